@@ -0,0 +1,358 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+/// A behavior tree for `AiBehavior` entities, loaded from a `.bt.ron` file.
+///
+/// The root is ticked every frame and starts over once it has finished.
+///
+/// ```ron
+/// (
+///     root: Sequence([
+///         Action(MoveToMarker("guard_post")),
+///         Wait(2.0),
+///         Selector([
+///             Sequence([
+///                 Action(LookAtInteractable(5.0)),
+///                 Action(PlayDialogue("greeting")),
+///             ]),
+///             Wait(1.0),
+///         ]),
+///     ]),
+/// )
+/// ```
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct BehaviorTree {
+    pub root: BehaviorNode,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum BehaviorNode {
+    /// runs its children in order until one fails
+    Sequence(Vec<BehaviorNode>),
+    /// runs its children in order until one succeeds
+    Selector(Vec<BehaviorNode>),
+    /// runs all its children every tick
+    Parallel {
+        policy: ParallelPolicy,
+        children: Vec<BehaviorNode>,
+    },
+    Invert(Box<BehaviorNode>),
+    /// succeeds once the child has finished, whatever the result
+    AlwaysSucceed(Box<BehaviorNode>),
+    /// runs the child `times` times, forever when unset, stopping at the first failure
+    Repeat {
+        times: Option<u32>,
+        child: Box<BehaviorNode>,
+    },
+    /// fails when the child takes longer than `seconds`
+    Timeout {
+        seconds: f32,
+        child: Box<BehaviorNode>,
+    },
+    Wait(f32),
+    /// succeeds when the blackboard entry is set and truthy
+    Check(String),
+    /// sets a blackboard entry and succeeds
+    Set(String, BlackboardValue),
+    Action(Action),
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParallelPolicy {
+    /// succeeds once every child has, fails as soon as one does
+    #[default]
+    RequireAll,
+    /// succeeds as soon as one child does, fails once every child has
+    RequireOne,
+}
+
+/// leaves that act on the world, carried out by `BehaviorActions`
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum Action {
+    /// walks to the named `LocationMarker`
+    MoveToMarker(String),
+    /// turns towards the closest `Interactable` within range, storing it as `"interactable"`
+    LookAtInteractable(f32),
+    /// sends a `DialogueRequested` for gameplay code to play
+    PlayDialogue(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    Success,
+    Failure,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum BlackboardValue {
+    Bool(bool),
+    Number(f32),
+    Text(String),
+    #[serde(skip)]
+    Position(Vec3),
+    #[serde(skip)]
+    Entity(Entity),
+}
+
+impl BlackboardValue {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            BlackboardValue::Bool(value) => *value,
+            BlackboardValue::Number(value) => *value != 0.0,
+            BlackboardValue::Text(value) => !value.is_empty(),
+            BlackboardValue::Position(_) | BlackboardValue::Entity(_) => true,
+        }
+    }
+}
+
+/// values shared between the nodes of a behavior tree and gameplay code
+#[derive(Component, Default, Clone, Debug)]
+pub struct Blackboard(pub HashMap<String, BlackboardValue>);
+
+impl Blackboard {
+    pub fn get(&self, key: &str) -> Option<&BlackboardValue> {
+        self.0.get(key)
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: BlackboardValue) {
+        self.0.insert(key.into(), value);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<BlackboardValue> {
+        self.0.remove(key)
+    }
+
+    pub fn is_truthy(&self, key: &str) -> bool {
+        self.get(key).is_some_and(BlackboardValue::is_truthy)
+    }
+}
+
+/// carries out the `Action` leaves of a tree
+pub trait BehaviorActions {
+    /// called every tick while the action runs, `first` is set on the tick it starts
+    fn run(&mut self, action: &Action, first: bool, blackboard: &mut Blackboard) -> Status;
+
+    /// called when a running action is cut short because a node above it finished, like a
+    /// `Timeout` running out or a `Parallel` resolving
+    fn halt(&mut self, _action: &Action) {}
+}
+
+/// where a tree is for one entity, nodes missing from it have not started
+#[derive(Default, Clone, Debug)]
+pub struct BehaviorMemory {
+    nodes: HashMap<usize, NodeMemory>,
+}
+
+#[derive(Default, Clone, Debug)]
+struct NodeMemory {
+    child: usize,
+    count: u32,
+    started_at: f32,
+    results: Vec<Option<Status>>,
+}
+
+impl BehaviorMemory {
+    /// stops every running node, the next tick starts from the root
+    pub fn reset(&mut self) {
+        self.nodes.clear();
+    }
+}
+
+impl BehaviorTree {
+    /// Ticks the tree once at `now` seconds, returning the status of the root.
+    pub fn tick(
+        &self,
+        memory: &mut BehaviorMemory,
+        blackboard: &mut Blackboard,
+        now: f32,
+        actions: &mut impl BehaviorActions,
+    ) -> Status {
+        let mut ticker = Ticker {
+            memory: &mut memory.nodes,
+            blackboard,
+            now,
+            actions,
+        };
+        ticker.tick(&self.root, 0)
+    }
+}
+
+impl BehaviorNode {
+    fn children(&self) -> &[BehaviorNode] {
+        match self {
+            BehaviorNode::Sequence(children)
+            | BehaviorNode::Selector(children)
+            | BehaviorNode::Parallel { children, .. } => children,
+            BehaviorNode::Invert(child)
+            | BehaviorNode::AlwaysSucceed(child)
+            | BehaviorNode::Repeat { child, .. }
+            | BehaviorNode::Timeout { child, .. } => std::slice::from_ref(child.as_ref()),
+            _ => &[],
+        }
+    }
+
+    /// number of nodes in the subtree, nodes are numbered depth first
+    fn len(&self) -> usize {
+        1 + self.children().iter().map(BehaviorNode::len).sum::<usize>()
+    }
+
+    /// ids of the children of the node with `id`
+    fn child_ids(&self, id: usize) -> Vec<usize> {
+        let mut next = id + 1;
+        self.children()
+            .iter()
+            .map(|child| {
+                let child_id = next;
+                next += child.len();
+                child_id
+            })
+            .collect()
+    }
+}
+
+struct Ticker<'a, A: BehaviorActions> {
+    memory: &'a mut HashMap<usize, NodeMemory>,
+    blackboard: &'a mut Blackboard,
+    now: f32,
+    actions: &'a mut A,
+}
+
+impl<A: BehaviorActions> Ticker<'_, A> {
+    /// forgets the node and everything under it, halting the actions still running under it
+    fn clear(&mut self, node: &BehaviorNode, id: usize) {
+        self.halt_running(node, id);
+        for id in id..id + node.len() {
+            self.memory.remove(&id);
+        }
+    }
+
+    /// halts the started actions under the node, finished nodes are already forgotten
+    fn halt_running(&mut self, node: &BehaviorNode, id: usize) {
+        for (child, child_id) in node.children().iter().zip(node.child_ids(id)) {
+            if !self.memory.contains_key(&child_id) {
+                continue;
+            }
+            match child {
+                BehaviorNode::Action(action) => self.actions.halt(action),
+                _ => self.halt_running(child, child_id),
+            }
+        }
+    }
+
+    fn finish(&mut self, node: &BehaviorNode, id: usize, status: Status) -> Status {
+        if status != Status::Running {
+            self.clear(node, id);
+        }
+        status
+    }
+
+    fn tick(&mut self, node: &BehaviorNode, id: usize) -> Status {
+        let first = !self.memory.contains_key(&id);
+        let now = self.now;
+        let started_at = self
+            .memory
+            .entry(id)
+            .or_insert_with(|| NodeMemory {
+                started_at: now,
+                ..default()
+            })
+            .started_at;
+        let children = node.children();
+        let ids = node.child_ids(id);
+
+        let status = match node {
+            BehaviorNode::Sequence(_) | BehaviorNode::Selector(_) => {
+                // a sequence goes on while children succeed, a selector while they fail
+                let go_on = match node {
+                    BehaviorNode::Sequence(_) => Status::Success,
+                    _ => Status::Failure,
+                };
+                let mut current = self.memory[&id].child;
+                loop {
+                    let Some(child) = children.get(current) else {
+                        break go_on;
+                    };
+                    match self.tick(child, ids[current]) {
+                        status if status == go_on => current += 1,
+                        Status::Running => {
+                            self.memory.entry(id).or_default().child = current;
+                            break Status::Running;
+                        }
+                        status => break status,
+                    }
+                }
+            }
+            BehaviorNode::Parallel { policy, .. } => {
+                let mut results = self.memory[&id].results.clone();
+                results.resize(children.len(), None);
+                for (index, child) in children.iter().enumerate() {
+                    if results[index].is_none() {
+                        let status = self.tick(child, ids[index]);
+                        results[index] = (status != Status::Running).then_some(status);
+                    }
+                }
+                let count = |status| results.iter().filter(|r| **r == Some(status)).count();
+                let (successes, failures) = (count(Status::Success), count(Status::Failure));
+                self.memory.entry(id).or_default().results = results;
+                match policy {
+                    ParallelPolicy::RequireAll if failures > 0 => Status::Failure,
+                    ParallelPolicy::RequireAll if successes == children.len() => Status::Success,
+                    ParallelPolicy::RequireOne if successes > 0 => Status::Success,
+                    ParallelPolicy::RequireOne if failures == children.len() => Status::Failure,
+                    _ => Status::Running,
+                }
+            }
+            BehaviorNode::Invert(child) => match self.tick(child, id + 1) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            BehaviorNode::AlwaysSucceed(child) => match self.tick(child, id + 1) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            BehaviorNode::Repeat { times, child } => match self.tick(child, id + 1) {
+                Status::Failure => Status::Failure,
+                Status::Success => {
+                    let memory = self.memory.entry(id).or_default();
+                    memory.count += 1;
+                    if times.is_some_and(|times| memory.count >= times) {
+                        Status::Success
+                    } else {
+                        // the child starts over on the next tick
+                        Status::Running
+                    }
+                }
+                Status::Running => Status::Running,
+            },
+            BehaviorNode::Timeout { seconds, child } => {
+                if now - started_at >= *seconds {
+                    Status::Failure
+                } else {
+                    self.tick(child, id + 1)
+                }
+            }
+            BehaviorNode::Wait(seconds) => {
+                if now - started_at >= *seconds {
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            BehaviorNode::Check(key) => {
+                if self.blackboard.is_truthy(key) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            BehaviorNode::Set(key, value) => {
+                self.blackboard.set(key.clone(), value.clone());
+                Status::Success
+            }
+            BehaviorNode::Action(action) => self.actions.run(action, first, self.blackboard),
+        };
+        self.finish(node, id, status)
+    }
+}
@@ -1,4 +1,258 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
 
-#[derive(Component)]
-pub struct AiController;
\ No newline at end of file
+use super::{
+    behavior::{BehaviorMemory, BehaviorTree, Blackboard, Status},
+    loader::AiProfile,
+};
+
+/// Runs the `AiProfile` at `profile` on the entity, add a `Navigator` for it to move around.
+///
+/// Can be set on blueprints from a Blenvy custom property.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+#[require(Transform, AiBrain)]
+pub struct AiController {
+    pub profile: String,
+}
+
+impl AiController {
+    pub fn new(profile: impl Into<String>) -> Self {
+        Self {
+            profile: profile.into(),
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AiState {
+    /// stands still
+    #[default]
+    Idle,
+    /// wanders around its home
+    Patrol,
+    /// follows the `Player`
+    Chase,
+    /// runs away from the `Player`
+    Flee,
+    /// stops and faces the `Player`, gameplay code takes it from there
+    Interact,
+}
+
+/// where an `AiController` is in its profile
+#[derive(Component, Default, Debug)]
+pub struct AiBrain {
+    pub state: AiState,
+    pub time_in_state: f32,
+    /// what the entity patrols around, its position when the profile was set
+    pub home: Vec3,
+    pub(crate) profile: Handle<AiProfile>,
+    pub(crate) started: bool,
+    /// the state was entered this frame
+    pub(crate) entered: bool,
+    /// seconds until the path to the player is refreshed
+    pub(crate) repath_in: f32,
+    /// the last path finished or could not be found
+    pub(crate) arrived: bool,
+    /// the last path got to its target, rather than none being found
+    pub(crate) reached: bool,
+}
+
+impl AiBrain {
+    pub(crate) fn enter(&mut self, state: AiState) {
+        self.state = state;
+        self.time_in_state = 0.0;
+        self.entered = true;
+        self.repath_in = 0.0;
+        self.arrived = false;
+        self.reached = false;
+    }
+}
+
+/// Runs the `BehaviorTree` at `tree` on the entity, an alternative to an `AiController` profile.
+///
+/// Can be set on blueprints from a Blenvy custom property.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+#[require(Transform, Blackboard, BehaviorRunner)]
+pub struct AiBehavior {
+    pub tree: String,
+}
+
+impl AiBehavior {
+    pub fn new(tree: impl Into<String>) -> Self {
+        Self { tree: tree.into() }
+    }
+}
+
+/// where an `AiBehavior` is in its tree
+#[derive(Component, Default, Debug)]
+pub struct BehaviorRunner {
+    /// status of the root after the last tick
+    pub status: Option<Status>,
+    pub(crate) tree: Handle<BehaviorTree>,
+    pub(crate) memory: BehaviorMemory,
+    /// outcome of the last path asked for by `Action::MoveToMarker`, `None` while walking
+    #[cfg(feature = "pathfind")]
+    pub(crate) path_result: Option<bool>,
+}
+
+/// Lets the entity notice `Player` and `Actor` entities, see `PerceptionMemory` for what it has.
+///
+/// Sight is blocked by level `CollisionShape`s, hearing goes through walls.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component, Default)]
+#[require(Transform, PerceptionMemory)]
+pub struct Perception {
+    /// width of the sight cone, in degrees
+    pub view_angle: f32,
+    /// how far the entity can see
+    pub range: f32,
+    /// entities closer than this are heard, even from behind
+    pub hearing_radius: f32,
+    /// height of the eyes above the origin, targets are looked at at the same height
+    pub eye_height: f32,
+    /// seconds a lost entity is remembered for
+    pub memory: f32,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            view_angle: 120.0,
+            range: 12.0,
+            hearing_radius: 3.0,
+            eye_height: 1.6,
+            memory: 10.0,
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sense {
+    Sight,
+    Hearing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sighting {
+    pub last_known: Vec3,
+    /// elapsed seconds at which the entity was last perceived
+    pub last_perceived: f32,
+    /// how the entity is perceived right now, `None` once it has been lost
+    pub sense: Option<Sense>,
+}
+
+/// what a `Perception` currently perceives and remembers
+#[derive(Component, Default, Debug)]
+pub struct PerceptionMemory(pub(crate) HashMap<Entity, Sighting>);
+
+impl PerceptionMemory {
+    pub fn get(&self, target: Entity) -> Option<&Sighting> {
+        self.0.get(&target)
+    }
+
+    pub fn perceives(&self, target: Entity) -> bool {
+        self.get(target)
+            .is_some_and(|sighting| sighting.sense.is_some())
+    }
+
+    /// where the entity is, or was when it was lost
+    pub fn last_known(&self, target: Entity) -> Option<Vec3> {
+        self.get(target).map(|sighting| sighting.last_known)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &Sighting)> {
+        self.0.iter().map(|(target, sighting)| (*target, sighting))
+    }
+}
+
+/// How a `PatrolRoute` carries on once its last marker is reached.
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatrolMode {
+    /// stops at the last marker
+    Once,
+    /// goes back to the first marker
+    #[default]
+    Loop,
+    /// walks the markers back in reverse
+    PingPong,
+}
+
+impl PatrolMode {
+    /// the marker after `index` when walking `forward` along `len` markers, and the new direction
+    pub fn next(self, index: usize, forward: bool, len: usize) -> Option<(usize, bool)> {
+        if len < 2 {
+            return None;
+        }
+        match (self, forward) {
+            (_, true) if index + 1 < len => Some((index + 1, true)),
+            (PatrolMode::PingPong, false) if index > 0 => Some((index - 1, false)),
+            (PatrolMode::Once, _) => None,
+            (PatrolMode::Loop, _) => Some((0, true)),
+            (PatrolMode::PingPong, true) => Some((len - 2, false)),
+            (PatrolMode::PingPong, false) => Some((1, true)),
+        }
+    }
+}
+
+/// An ordered chain of `LocationMarker`s, by name, for `Patrol` entities to walk.
+///
+/// Can be placed on any level node from a Blenvy custom property.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct PatrolRoute {
+    pub name: String,
+    pub markers: Vec<String>,
+    pub mode: PatrolMode,
+}
+
+/// What a patrol does at a `LocationMarker` on its route, set from a Blenvy custom property.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct PatrolPoint {
+    /// seconds spent at the marker before moving on
+    pub wait: f32,
+    /// world space direction to face while waiting, the forward of the marker when `None`
+    pub look: Option<Vec3>,
+}
+
+/// Walks the `PatrolRoute` named `route` while the `AiController` is in `AiState::Patrol`,
+/// instead of wandering around its home.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+#[require(Transform, PatrolProgress)]
+pub struct Patrol {
+    pub route: String,
+}
+
+impl Patrol {
+    pub fn new(route: impl Into<String>) -> Self {
+        Self {
+            route: route.into(),
+        }
+    }
+}
+
+/// where a `Patrol` is along its route
+#[derive(Component, Debug)]
+pub struct PatrolProgress {
+    /// index of the marker walked to, or waited at
+    pub index: usize,
+    pub(crate) forward: bool,
+    /// seconds left to wait at the marker, `None` while walking
+    pub waiting: Option<f32>,
+    /// the route has been walked to its end
+    pub finished: bool,
+}
+
+impl Default for PatrolProgress {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            forward: true,
+            waiting: None,
+            finished: false,
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use bevy::prelude::*;
+
+use super::components::{AiState, Sense};
+
+/// an `AiController` moved to another state of its profile
+#[derive(Event, Debug, Clone)]
+pub struct AiStateChanged {
+    pub entity: Entity,
+    pub from: AiState,
+    pub to: AiState,
+}
+
+/// a behavior tree asked for `dialogue` to be played by `entity`
+#[derive(Event, Debug, Clone)]
+pub struct DialogueRequested {
+    pub entity: Entity,
+    pub dialogue: String,
+}
+
+/// a `Perception` noticed `target`
+#[derive(Event, Debug, Clone)]
+pub struct Perceived {
+    pub observer: Entity,
+    pub target: Entity,
+    pub sense: Sense,
+}
+
+/// a `Perception` can no longer see or hear `target`, it is remembered for a while
+#[derive(Event, Debug, Clone)]
+pub struct Lost {
+    pub observer: Entity,
+    pub target: Entity,
+    pub last_known: Vec3,
+}
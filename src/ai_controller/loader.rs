@@ -0,0 +1,194 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+use crate::assembler::loader::RonLoaderError;
+
+use super::{behavior::BehaviorTree, components::AiState};
+
+/// Behaviour of `AiController` entities, loaded from a `.ai.ron` file.
+///
+/// ```ron
+/// (
+///     initial: Patrol,
+///     sight_range: 12.0,
+///     states: {
+///         Patrol: (
+///             range: 6.0,
+///             transitions: [(to: Chase, when: SeesPlayer)],
+///         ),
+///         Chase: (
+///             speed: Some(4.0),
+///             transitions: [
+///                 (to: Interact, when: PlayerWithin(1.5)),
+///                 (to: Patrol, when: PlayerBeyond(15.0)),
+///             ],
+///         ),
+///         Interact: (
+///             transitions: [(to: Chase, when: PlayerBeyond(2.0))],
+///         ),
+///     },
+/// )
+/// ```
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct AiProfile {
+    #[serde(default)]
+    pub initial: AiState,
+    /// how far the `Player` can be seen from, for entities without a `Perception`
+    #[serde(default = "default_sight_range")]
+    pub sight_range: f32,
+    /// width of the sight cone, in degrees
+    #[serde(default = "default_sight_angle")]
+    pub sight_angle: f32,
+    #[serde(default)]
+    pub states: HashMap<AiState, StateConfig>,
+}
+
+fn default_sight_range() -> f32 {
+    10.0
+}
+
+fn default_sight_angle() -> f32 {
+    120.0
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct StateConfig {
+    /// `Navigator` speed while in the state, left as it is when unset
+    #[serde(default)]
+    pub speed: Option<f32>,
+    /// patrol radius around home, or how far to run when fleeing
+    #[serde(default = "default_range")]
+    pub range: f32,
+    /// seconds between path requests while chasing or fleeing
+    #[serde(default = "default_repath")]
+    pub repath: f32,
+    /// checked in order, the first one that holds is taken
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+}
+
+impl Default for StateConfig {
+    fn default() -> Self {
+        Self {
+            speed: None,
+            range: default_range(),
+            repath: default_repath(),
+            transitions: Vec::new(),
+        }
+    }
+}
+
+fn default_range() -> f32 {
+    8.0
+}
+
+fn default_repath() -> f32 {
+    0.5
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Transition {
+    pub to: AiState,
+    pub when: Condition,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum Condition {
+    PlayerWithin(f32),
+    PlayerBeyond(f32),
+    /// the `Perception` of the entity perceives the `Player`, or without one the `Player` is in
+    /// the sight cone of the profile
+    SeesPlayer,
+    /// the `Player` is perceived or still remembered
+    RemembersPlayer,
+    /// seconds spent in the current state
+    After(f32),
+    /// the last path has been walked, or could not be found
+    Arrived,
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+/// what an `AiController` knows when checking its transitions
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AiContext {
+    /// `None` when there is no `Player`
+    pub player_distance: Option<f32>,
+    pub sees_player: bool,
+    pub remembers_player: bool,
+    pub time_in_state: f32,
+    pub arrived: bool,
+}
+
+impl Condition {
+    pub fn holds(&self, context: &AiContext) -> bool {
+        match self {
+            Condition::PlayerWithin(distance) => {
+                context.player_distance.is_some_and(|d| d <= *distance)
+            }
+            Condition::PlayerBeyond(distance) => {
+                context.player_distance.is_none_or(|d| d > *distance)
+            }
+            Condition::SeesPlayer => context.sees_player,
+            Condition::RemembersPlayer => context.remembers_player,
+            Condition::After(seconds) => context.time_in_state >= *seconds,
+            Condition::Arrived => context.arrived,
+            Condition::Not(condition) => !condition.holds(context),
+            Condition::All(conditions) => conditions.iter().all(|c| c.holds(context)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.holds(context)),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AiProfileLoader;
+
+impl AssetLoader for AiProfileLoader {
+    type Asset = AiProfile;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ai.ron"]
+    }
+}
+
+#[derive(Default)]
+pub struct BehaviorTreeLoader;
+
+impl AssetLoader for BehaviorTreeLoader {
+    type Asset = BehaviorTree;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bt.ron"]
+    }
+}
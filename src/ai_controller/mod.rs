@@ -1,11 +1,67 @@
+#[cfg(feature = "pathfind")]
+use crate::pathfind::events::{PathBlocked, PathCompleted, PathEvent, PathNotFound};
+use crate::{location_marker::resources::LocationMarkers, resources::HammerspaceConfig};
+use behavior::BehaviorTree;
 use bevy::prelude::*;
+use components::{
+    AiBehavior, AiController, AiState, Patrol, PatrolMode, PatrolPoint, PatrolRoute, Perception,
+    Sense,
+};
+use events::{AiStateChanged, DialogueRequested, Lost, Perceived};
+use loader::{AiProfile, AiProfileLoader, BehaviorTreeLoader};
+#[cfg(feature = "pathfind")]
+use systems::{act, patrol, track_arrivals};
+use systems::{
+    load_behaviors, load_profiles, perceive, share_perception, tick_behaviors, update_states,
+};
 
-
+pub mod behavior;
 pub mod components;
+pub mod events;
+pub mod loader;
+pub mod systems;
 
 pub struct AiControllerPlugin;
 
 impl Plugin for AiControllerPlugin {
-    fn build(&self, _app: &mut App) {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<AiProfile>()
+            .init_asset_loader::<AiProfileLoader>()
+            .init_asset::<BehaviorTree>()
+            .init_asset_loader::<BehaviorTreeLoader>()
+            .init_resource::<HammerspaceConfig>()
+            .init_resource::<LocationMarkers>()
+            .add_event::<AiStateChanged>()
+            .add_event::<DialogueRequested>()
+            .add_event::<Perceived>()
+            .add_event::<Lost>()
+            .add_systems(
+                Update,
+                (
+                    (load_profiles, load_behaviors),
+                    perceive,
+                    share_perception,
+                    #[cfg(feature = "pathfind")]
+                    track_arrivals,
+                    (update_states, tick_behaviors),
+                    #[cfg(feature = "pathfind")]
+                    (act, patrol).chain(),
+                )
+                    .chain(),
+            )
+            .register_type::<AiController>()
+            .register_type::<AiState>()
+            .register_type::<AiBehavior>()
+            .register_type::<Perception>()
+            .register_type::<Sense>()
+            .register_type::<Patrol>()
+            .register_type::<PatrolRoute>()
+            .register_type::<PatrolMode>()
+            .register_type::<PatrolPoint>();
+        #[cfg(feature = "pathfind")]
+        app.add_event::<PathEvent>()
+            .add_event::<PathCompleted>()
+            .add_event::<PathNotFound>()
+            .add_event::<PathBlocked>();
     }
 }
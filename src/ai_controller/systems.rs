@@ -0,0 +1,609 @@
+use bevy::prelude::*;
+#[cfg(feature = "pathfind")]
+use rand::Rng;
+
+#[cfg(feature = "pathfind")]
+use crate::pathfind::{
+    components::{FindingPath, Navigator, PathNodes},
+    events::{PathBlocked, PathCompleted, PathEvent, PathNotFound},
+};
+use crate::{
+    assembler::components::CollisionShape,
+    interact::components::{Actor, Interactable, Player},
+    location_marker::{components::LocationMarker, resources::LocationMarkers},
+    resources::HammerspaceConfig,
+};
+
+use super::{
+    behavior::{Action, BehaviorActions, BehaviorTree, Blackboard, BlackboardValue, Status},
+    components::{
+        AiBehavior, AiBrain, AiController, BehaviorRunner, Perception, PerceptionMemory, Sense,
+        Sighting,
+    },
+    events::{AiStateChanged, DialogueRequested, Lost, Perceived},
+    loader::{AiContext, AiProfile},
+};
+#[cfg(feature = "pathfind")]
+use super::{
+    components::{AiState, Patrol, PatrolPoint, PatrolProgress, PatrolRoute},
+    loader::StateConfig,
+};
+
+/// loads the profile of new or changed controllers, restarting them from its initial state
+pub fn load_profiles(
+    mut ai_q: Query<(&AiController, &mut AiBrain, &Transform), Changed<AiController>>,
+    server: Res<AssetServer>,
+) {
+    for (controller, mut brain, transform) in ai_q.iter_mut() {
+        brain.profile = server.load(&controller.profile);
+        brain.home = transform.translation;
+        brain.started = false;
+    }
+}
+
+/// takes the first transition of the current state that holds
+pub fn update_states(
+    mut ai_q: Query<(
+        Entity,
+        &mut AiBrain,
+        &GlobalTransform,
+        Option<&PerceptionMemory>,
+    )>,
+    mut changed_ev: EventWriter<AiStateChanged>,
+    player_q: Query<(Entity, &GlobalTransform), With<Player>>,
+    profiles: Res<Assets<AiProfile>>,
+    time: Res<Time>,
+) {
+    let player = player_q.iter().next();
+
+    for (entity, mut brain, transform, memory) in ai_q.iter_mut() {
+        let Some(profile) = profiles.get(&brain.profile) else {
+            continue;
+        };
+        brain.entered = false;
+        if !brain.started {
+            brain.started = true;
+            brain.enter(profile.initial);
+            continue;
+        }
+        brain.time_in_state += time.delta_secs();
+
+        let position = transform.translation();
+        let to_player = player.map(|(_, player)| player.translation() - position);
+        // without a `Perception` the player is seen whenever it is in the cone of the profile
+        let sees_player = match (memory, player) {
+            (Some(memory), Some((player, _))) => memory.perceives(player),
+            (Some(_), None) => false,
+            (None, _) => to_player.is_some_and(|to_player| {
+                let flat = to_player.xz();
+                let facing = transform.forward().xz();
+                to_player.length() <= profile.sight_range
+                    && (flat.length_squared() < f32::EPSILON
+                        || facing.angle_to(flat).abs().to_degrees() <= profile.sight_angle / 2.0)
+            }),
+        };
+        let remembers_player = match (memory, player) {
+            (Some(memory), Some((player, _))) => memory.get(player).is_some(),
+            _ => sees_player,
+        };
+        let context = AiContext {
+            player_distance: to_player.map(Vec3::length),
+            sees_player,
+            remembers_player,
+            time_in_state: brain.time_in_state,
+            arrived: brain.arrived,
+        };
+        let next = profile.states.get(&brain.state).and_then(|state| {
+            state
+                .transitions
+                .iter()
+                .find(|transition| transition.to != brain.state && transition.when.holds(&context))
+                .map(|transition| transition.to)
+        });
+        if let Some(next) = next {
+            changed_ev.send(AiStateChanged {
+                entity,
+                from: brain.state,
+                to: next,
+            });
+            brain.enter(next);
+        }
+    }
+}
+
+/// notes when controllers are done with their path, for `Condition::Arrived`, patrols and
+/// `Action::MoveToMarker`
+#[cfg(feature = "pathfind")]
+pub fn track_arrivals(
+    mut ai_q: Query<&mut AiBrain>,
+    mut runner_q: Query<&mut BehaviorRunner>,
+    mut completed_ev: EventReader<PathCompleted>,
+    mut not_found_ev: EventReader<PathNotFound>,
+    mut blocked_ev: EventReader<PathBlocked>,
+) {
+    // a blocked path ends the walk like one that was never found
+    let done = completed_ev
+        .read()
+        .map(|ev| (ev.0, true))
+        .chain(not_found_ev.read().map(|ev| (ev.0, false)))
+        .chain(blocked_ev.read().map(|ev| (ev.0, false)));
+    for (entity, found) in done {
+        if let Ok(mut brain) = ai_q.get_mut(entity) {
+            brain.arrived = true;
+            brain.reached = found;
+        }
+        if let Ok(mut runner) = runner_q.get_mut(entity) {
+            runner.path_result = Some(found);
+        }
+    }
+}
+
+/// carries out the current state of each controller, asking the pathfind module for paths
+#[cfg(feature = "pathfind")]
+pub fn act(
+    mut commands: Commands,
+    mut ai_q: Query<(
+        Entity,
+        &mut AiBrain,
+        &mut Transform,
+        Option<&mut Navigator>,
+        Option<&PerceptionMemory>,
+        Has<Patrol>,
+    )>,
+    mut path_ev: EventWriter<PathEvent>,
+    player_q: Query<(Entity, &GlobalTransform), With<Player>>,
+    profiles: Res<Assets<AiProfile>>,
+    time: Res<Time>,
+) {
+    let fallback = StateConfig::default();
+
+    for (entity, mut brain, mut transform, navigator, memory, patrols) in ai_q.iter_mut() {
+        // with a `Perception` the entity goes by where it last noticed the player
+        let player = player_q
+            .iter()
+            .next()
+            .and_then(|(player, player_transform)| match memory {
+                Some(memory) if !memory.perceives(player) => memory.last_known(player),
+                _ => Some(player_transform.translation()),
+            });
+        let Some(profile) = profiles.get(&brain.profile) else {
+            continue;
+        };
+        let config = profile.states.get(&brain.state).unwrap_or(&fallback);
+        if brain.entered {
+            if let (Some(speed), Some(mut navigator)) = (config.speed, navigator) {
+                navigator.speed = speed;
+            }
+        }
+        brain.repath_in -= time.delta_secs();
+        let position = transform.translation;
+        let repath = brain.repath_in <= 0.0;
+
+        match (brain.state, player) {
+            (AiState::Idle, _) | (AiState::Interact, None) => {
+                if brain.entered {
+                    commands.entity(entity).remove::<PathNodes>();
+                }
+            }
+            (AiState::Patrol, _) if !patrols => {
+                if brain.entered || brain.arrived {
+                    brain.arrived = false;
+                    let target = pick_point_in_range(brain.home, config.range);
+                    path_ev.send(PathEvent(entity, position, target));
+                }
+            }
+            (AiState::Chase, Some(player)) if repath => {
+                brain.repath_in = config.repath;
+                path_ev.send(PathEvent(entity, position, player));
+            }
+            (AiState::Flee, Some(player)) if repath => {
+                brain.repath_in = config.repath;
+                let away = (position - player).with_y(0.0).normalize_or(Vec3::X);
+                path_ev.send(PathEvent(entity, position, position + away * config.range));
+            }
+            (AiState::Interact, Some(player)) => {
+                if brain.entered {
+                    commands.entity(entity).remove::<PathNodes>();
+                }
+                let to_player = (player - position).with_y(0.0);
+                if to_player.length_squared() > f32::EPSILON {
+                    transform.look_to(to_player, Vec3::Y);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Walks each `Patrol` in `AiState::Patrol` along its `PatrolRoute`, waiting at every marker
+/// for as long as its `PatrolPoint` says.
+#[cfg(feature = "pathfind")]
+#[allow(clippy::too_many_arguments)]
+pub fn patrol(
+    mut ai_q: Query<(
+        Entity,
+        &mut AiBrain,
+        Ref<Patrol>,
+        &mut PatrolProgress,
+        &mut Transform,
+    )>,
+    mut path_ev: EventWriter<PathEvent>,
+    route_q: Query<&PatrolRoute>,
+    marker_q: Query<(&GlobalTransform, Option<&PatrolPoint>), With<LocationMarker>>,
+    markers: Res<LocationMarkers>,
+    config: Res<HammerspaceConfig>,
+    time: Res<Time>,
+) {
+    let marker = |name: &str| {
+        let key = HammerspaceConfig::strip_identifier(name, &config.spawn_identifier);
+        match markers.get(key) {
+            [marker] => marker_q.get(*marker).ok(),
+            _ => None,
+        }
+    };
+
+    for (entity, mut brain, patrol, mut progress, mut transform) in ai_q.iter_mut() {
+        if patrol.is_changed() {
+            *progress = PatrolProgress::default();
+        }
+        if brain.state != AiState::Patrol || progress.finished {
+            continue;
+        }
+        let Some(route) = route_q.iter().find(|route| route.name == patrol.route) else {
+            if brain.entered {
+                warn!("{} cannot patrol unknown route {}", entity, patrol.route);
+            }
+            continue;
+        };
+        let position = transform.translation;
+        // heads for the marker at `index`, skipping along the route past markers that cannot be
+        // found, twice the length of the route covers a ping pong in both directions
+        let mut walk_to = |progress: &mut PatrolProgress, mut index: usize, mut forward: bool| {
+            for _ in 0..route.markers.len() * 2 {
+                match route.markers.get(index).and_then(|name| marker(name)) {
+                    Some((target, _)) => {
+                        progress.index = index;
+                        progress.forward = forward;
+                        path_ev.send(PathEvent(entity, position, target.translation()));
+                        return;
+                    }
+                    None => warn!(
+                        "{} cannot patrol to unknown marker {:?}",
+                        entity,
+                        route.markers.get(index)
+                    ),
+                }
+                match route.mode.next(index, forward, route.markers.len()) {
+                    Some(next) => (index, forward) = next,
+                    None => break,
+                }
+            }
+            progress.finished = true;
+        };
+
+        if brain.entered {
+            // picks the route back up from the marker it was heading for
+            let index = progress.index.min(route.markers.len().saturating_sub(1));
+            let forward = progress.forward;
+            progress.waiting = None;
+            walk_to(&mut *progress, index, forward);
+            continue;
+        }
+        if brain.arrived && !brain.reached {
+            // the marker could not be reached, move on without waiting there
+            brain.arrived = false;
+            progress.waiting = Some(0.0);
+        } else if brain.arrived {
+            brain.arrived = false;
+            let (at, point) = route
+                .markers
+                .get(progress.index)
+                .and_then(|name| marker(name))
+                .unzip();
+            let point = point.flatten().cloned().unwrap_or_default();
+            if point.wait > 0.0 {
+                let look = point
+                    .look
+                    .or_else(|| at.map(|at| at.forward().as_vec3()))
+                    .map(|look| look.with_y(0.0))
+                    .filter(|look| look.length_squared() > f32::EPSILON);
+                if let Some(look) = look {
+                    transform.look_to(look, Vec3::Y);
+                }
+            }
+            progress.waiting = Some(point.wait);
+        }
+        let Some(waiting) = progress.waiting.as_mut() else {
+            continue;
+        };
+        *waiting -= time.delta_secs();
+        if *waiting > 0.0 {
+            continue;
+        }
+        progress.waiting = None;
+        match route
+            .mode
+            .next(progress.index, progress.forward, route.markers.len())
+        {
+            Some((index, forward)) => walk_to(&mut *progress, index, forward),
+            None => progress.finished = true,
+        }
+    }
+}
+
+/// random point on the ground within `range` of `center`
+#[cfg(feature = "pathfind")]
+fn pick_point_in_range(center: Vec3, range: f32) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    let distance = range * rng.gen::<f32>().sqrt();
+    center + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance
+}
+
+/// loads the tree of new or changed behaviors, starting them from the root
+pub fn load_behaviors(
+    mut behavior_q: Query<(&AiBehavior, &mut BehaviorRunner), Changed<AiBehavior>>,
+    server: Res<AssetServer>,
+) {
+    for (behavior, mut runner) in behavior_q.iter_mut() {
+        runner.tree = server.load(&behavior.tree);
+        runner.memory.reset();
+        runner.status = None;
+    }
+}
+
+/// ticks the behavior tree of every `AiBehavior` once
+#[allow(clippy::too_many_arguments)]
+pub fn tick_behaviors(
+    #[cfg(feature = "pathfind")] mut commands: Commands,
+    mut behavior_q: Query<(Entity, &mut BehaviorRunner, &mut Blackboard, &mut Transform)>,
+    interactable_q: Query<(Entity, &GlobalTransform), With<Interactable>>,
+    marker_q: Query<&GlobalTransform, With<LocationMarker>>,
+    mut dialogue_ev: EventWriter<DialogueRequested>,
+    #[cfg(feature = "pathfind")] mut path_ev: EventWriter<PathEvent>,
+    markers: Res<LocationMarkers>,
+    config: Res<HammerspaceConfig>,
+    trees: Res<Assets<BehaviorTree>>,
+    time: Res<Time>,
+) {
+    let interactables: Vec<(Entity, Vec3)> = interactable_q
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation()))
+        .collect();
+    let marker_position = |name: &str| {
+        let key = HammerspaceConfig::strip_identifier(name, &config.spawn_identifier);
+        match markers.get(key) {
+            [marker] => marker_q.get(*marker).ok().map(GlobalTransform::translation),
+            _ => None,
+        }
+    };
+
+    for (entity, mut runner, mut blackboard, mut transform) in behavior_q.iter_mut() {
+        let runner = &mut *runner;
+        let Some(tree) = trees.get(&runner.tree) else {
+            continue;
+        };
+        let position = transform.translation;
+        let mut actions = WorldActions {
+            entity,
+            position,
+            transform: &mut transform,
+            interactables: &interactables,
+            marker_position: &marker_position,
+            #[cfg(feature = "pathfind")]
+            path_result: &mut runner.path_result,
+            #[cfg(feature = "pathfind")]
+            path: None,
+            #[cfg(feature = "pathfind")]
+            halted: false,
+            dialogues: Vec::new(),
+        };
+        let status = tree.tick(
+            &mut runner.memory,
+            &mut blackboard,
+            time.elapsed_secs(),
+            &mut actions,
+        );
+        #[cfg(feature = "pathfind")]
+        if let Some(target) = actions.path {
+            path_ev.send(PathEvent(entity, position, target));
+        } else if actions.halted {
+            commands.entity(entity).remove::<(PathNodes, FindingPath)>();
+        }
+        for dialogue in actions.dialogues {
+            dialogue_ev.send(DialogueRequested { entity, dialogue });
+        }
+        runner.status = Some(status);
+    }
+}
+
+/// carries out the tree actions of one entity, events are sent once the tree has been ticked
+struct WorldActions<'a> {
+    entity: Entity,
+    position: Vec3,
+    transform: &'a mut Transform,
+    interactables: &'a [(Entity, Vec3)],
+    marker_position: &'a dyn Fn(&str) -> Option<Vec3>,
+    #[cfg(feature = "pathfind")]
+    path_result: &'a mut Option<bool>,
+    #[cfg(feature = "pathfind")]
+    path: Option<Vec3>,
+    /// a move was halted, the entity stops walking unless a new one starts
+    #[cfg(feature = "pathfind")]
+    halted: bool,
+    dialogues: Vec<String>,
+}
+
+impl WorldActions<'_> {
+    #[cfg(feature = "pathfind")]
+    fn move_to_marker(&mut self, marker: &str, first: bool) -> Status {
+        if first {
+            let Some(target) = (self.marker_position)(marker) else {
+                warn!("{} cannot move to unknown marker {}", self.entity, marker);
+                return Status::Failure;
+            };
+            *self.path_result = None;
+            self.path = Some(target);
+            return Status::Running;
+        }
+        match self.path_result {
+            None => Status::Running,
+            Some(true) => Status::Success,
+            Some(false) => Status::Failure,
+        }
+    }
+
+    /// without pathfinding the entity is put at the marker straight away
+    #[cfg(not(feature = "pathfind"))]
+    fn move_to_marker(&mut self, marker: &str, _first: bool) -> Status {
+        let Some(target) = (self.marker_position)(marker) else {
+            warn!("{} cannot move to unknown marker {}", self.entity, marker);
+            return Status::Failure;
+        };
+        self.transform.translation = target;
+        Status::Success
+    }
+}
+
+impl BehaviorActions for WorldActions<'_> {
+    #[cfg(feature = "pathfind")]
+    fn halt(&mut self, action: &Action) {
+        if let Action::MoveToMarker(_) = action {
+            self.halted = true;
+        }
+    }
+
+    fn run(&mut self, action: &Action, first: bool, blackboard: &mut Blackboard) -> Status {
+        match action {
+            Action::MoveToMarker(marker) => self.move_to_marker(marker, first),
+            Action::LookAtInteractable(range) => {
+                let closest = self
+                    .interactables
+                    .iter()
+                    .filter(|(entity, _)| *entity != self.entity)
+                    .map(|(entity, point)| (*entity, *point, point.distance(self.position)))
+                    .filter(|(_, _, distance)| distance <= range)
+                    .min_by(|a, b| a.2.total_cmp(&b.2));
+                let Some((target, point, _)) = closest else {
+                    blackboard.remove("interactable");
+                    return Status::Failure;
+                };
+                let to_target = (point - self.position).with_y(0.0);
+                if to_target.length_squared() > f32::EPSILON {
+                    self.transform.look_to(to_target, Vec3::Y);
+                }
+                blackboard.set("interactable", BlackboardValue::Entity(target));
+                Status::Success
+            }
+            Action::PlayDialogue(dialogue) => {
+                self.dialogues.push(dialogue.clone());
+                Status::Success
+            }
+        }
+    }
+}
+
+/// Updates what each `Perception` sees and hears of `Player` and `Actor` entities.
+///
+/// Sight needs the target in the view cone, within range and with no level `CollisionShape` in
+/// between; colliders around either end of the line of sight are ignored.
+pub fn perceive(
+    mut observer_q: Query<(Entity, &Perception, &mut PerceptionMemory, &GlobalTransform)>,
+    target_q: Query<(Entity, &GlobalTransform), With<Actor>>,
+    collider_q: Query<(&CollisionShape, &GlobalTransform)>,
+    mut perceived_ev: EventWriter<Perceived>,
+    mut lost_ev: EventWriter<Lost>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs();
+    let colliders: Vec<_> = collider_q.iter().collect();
+
+    for (observer, perception, mut memory, transform) in observer_q.iter_mut() {
+        let eye = transform.translation() + Vec3::Y * perception.eye_height;
+        let facing = transform.forward().xz();
+        let mut noticed = Vec::new();
+
+        for (target, target_transform) in target_q.iter() {
+            if target == observer {
+                continue;
+            }
+            let position = target_transform.translation();
+            let point = position + Vec3::Y * perception.eye_height;
+            let to_target = point - eye;
+            let distance = to_target.length();
+            let flat = to_target.xz();
+            let in_cone = flat.length_squared() < f32::EPSILON
+                || facing.angle_to(flat).abs().to_degrees() <= perception.view_angle / 2.0;
+            let in_sight = distance <= perception.range
+                && in_cone
+                && !colliders.iter().any(|(shape, collider)| {
+                    !shape.contains(collider, eye)
+                        && !shape.contains(collider, point)
+                        && shape.blocks(collider, eye, point)
+                });
+            let sense = if in_sight {
+                Sense::Sight
+            } else if distance <= perception.hearing_radius {
+                Sense::Hearing
+            } else {
+                continue;
+            };
+            noticed.push(target);
+            let sighting = Sighting {
+                last_known: position,
+                last_perceived: now,
+                sense: Some(sense),
+            };
+            if let Some(previous) = memory.0.insert(target, sighting) {
+                if previous.sense.is_some() {
+                    continue;
+                }
+            }
+            perceived_ev.send(Perceived {
+                observer,
+                target,
+                sense,
+            });
+        }
+
+        for (target, sighting) in memory.0.iter_mut() {
+            if sighting.sense.is_some() && !noticed.contains(target) {
+                sighting.sense = None;
+                lost_ev.send(Lost {
+                    observer,
+                    target: *target,
+                    last_known: sighting.last_known,
+                });
+            }
+        }
+        memory.0.retain(|_, sighting| {
+            sighting.sense.is_some() || now - sighting.last_perceived <= perception.memory
+        });
+    }
+}
+
+/// Shares what behavior trees need from `PerceptionMemory` on their `Blackboard`.
+///
+/// Sets `"player_perceived"` and, while the player is remembered, `"player_last_known"`.
+pub fn share_perception(
+    mut behavior_q: Query<(&PerceptionMemory, &mut Blackboard), Changed<PerceptionMemory>>,
+    player_q: Query<Entity, With<Player>>,
+) {
+    let Some(player) = player_q.iter().next() else {
+        return;
+    };
+    for (memory, mut blackboard) in behavior_q.iter_mut() {
+        blackboard.set(
+            "player_perceived",
+            BlackboardValue::Bool(memory.perceives(player)),
+        );
+        match memory.last_known(player) {
+            Some(position) => {
+                blackboard.set("player_last_known", BlackboardValue::Position(position))
+            }
+            None => {
+                blackboard.remove("player_last_known");
+            }
+        }
+    }
+}
@@ -3,8 +3,187 @@ use bevy::prelude::*;
 #[derive(Component)]
 pub struct LevelTerrain;
 
+/// root of a sub-level streamed in from the `ChunkRegistry`
+#[derive(Component)]
+#[require(LevelScoped)]
+pub struct LevelChunk(pub String);
+
+/// entities outside of the game world hierarchy that should be despawned along with the level
+#[derive(Component, Default)]
+pub struct LevelScoped;
+
 #[derive(Component, Default)]
 pub struct Character;
 
 #[derive(Component, Default, Reflect)]
 pub struct MaterialMarker(pub String);
+
+/// Collision geometry taken from a level node whose name ends in the configured
+/// `collision_identifier`, in the node's local space. The node itself is hidden.
+#[derive(Component, Clone, Debug)]
+pub enum CollisionShape {
+    Cuboid {
+        center: Vec3,
+        half_extents: Vec3,
+    },
+    /// the convex hull of the collider mesh seen from above, extruded over its height
+    ConvexHull {
+        /// hull corners on the xz plane, counter clockwise seen from below
+        outline: Vec<Vec2>,
+        min_y: f32,
+        max_y: f32,
+    },
+}
+
+impl CollisionShape {
+    /// The shape around the vertices of a collider mesh, its convex hull when `convex` is set
+    /// and its bounding box otherwise. `None` without any points.
+    pub fn from_points(points: &[Vec3], convex: bool) -> Option<Self> {
+        let (min, max) = points.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+        if points.is_empty() {
+            None
+        } else if convex {
+            Some(CollisionShape::ConvexHull {
+                outline: convex_outline(points.iter().map(|point| point.xz()).collect()),
+                min_y: min.y,
+                max_y: max.y,
+            })
+        } else {
+            Some(CollisionShape::Cuboid {
+                center: (min + max) / 2.0,
+                half_extents: (max - min) / 2.0,
+            })
+        }
+    }
+
+    /// local space bounds of the shape as `(min, max)`
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        match self {
+            CollisionShape::Cuboid {
+                center,
+                half_extents,
+            } => (*center - *half_extents, *center + *half_extents),
+            CollisionShape::ConvexHull {
+                outline,
+                min_y,
+                max_y,
+            } => {
+                let (min, max) = outline.iter().fold(
+                    (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                    |(min, max), point| (min.min(*point), max.max(*point)),
+                );
+                (
+                    Vec3::new(min.x, *min_y, min.y),
+                    Vec3::new(max.x, *max_y, max.y),
+                )
+            }
+        }
+    }
+
+    /// the outline of a hull that encloses an area, flat hulls are treated as their bounds
+    fn hull_outline(&self) -> Option<&[Vec2]> {
+        match self {
+            CollisionShape::ConvexHull { outline, .. } if outline.len() >= 3 => Some(outline),
+            _ => None,
+        }
+    }
+
+    /// Whether the world space `point` is inside the shape.
+    pub fn contains(&self, transform: &GlobalTransform, point: Vec3) -> bool {
+        let (min, max) = self.bounds();
+        let local = transform.affine().inverse().transform_point3(point);
+        if !(local.cmpge(min).all() && local.cmple(max).all()) {
+            return false;
+        }
+        let Some(outline) = self.hull_outline() else {
+            return true;
+        };
+        let point = local.xz();
+        outline
+            .iter()
+            .zip(outline.iter().cycle().skip(1))
+            .all(|(a, b)| (*b - *a).perp_dot(point - *a) >= 0.0)
+    }
+
+    /// Whether the world space segment from `from` to `to` passes through the shape.
+    pub fn blocks(&self, transform: &GlobalTransform, from: Vec3, to: Vec3) -> bool {
+        let (min, max) = self.bounds();
+        let inverse = transform.affine().inverse();
+        let from = inverse.transform_point3(from);
+        let direction = inverse.transform_point3(to) - from;
+        // clip the segment against each pair of faces of the bounds in turn
+        let (mut enter, mut exit) = (0.0f32, 1.0f32);
+        for axis in 0..3 {
+            if direction[axis].abs() < f32::EPSILON {
+                if from[axis] < min[axis] || from[axis] > max[axis] {
+                    return false;
+                }
+                continue;
+            }
+            let near = (min[axis] - from[axis]) / direction[axis];
+            let far = (max[axis] - from[axis]) / direction[axis];
+            enter = enter.max(near.min(far));
+            exit = exit.min(near.max(far));
+            if enter > exit {
+                return false;
+            }
+        }
+        let Some(outline) = self.hull_outline() else {
+            return true;
+        };
+        // then against the side of the hull behind each outline edge
+        let (from, direction) = (from.xz(), direction.xz());
+        for (a, b) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+            let edge = *b - *a;
+            let inside = edge.perp_dot(from - *a);
+            let towards = edge.perp_dot(direction);
+            if towards.abs() < f32::EPSILON {
+                if inside < 0.0 {
+                    return false;
+                }
+                continue;
+            }
+            let crossing = -inside / towards;
+            if towards > 0.0 {
+                enter = enter.max(crossing);
+            } else {
+                exit = exit.min(crossing);
+            }
+            if enter > exit {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Convex hull of `points` by Andrew's monotone chain, with a positive `perp_dot` from each
+/// edge towards the inside.
+fn convex_outline(mut points: Vec<Vec2>) -> Vec<Vec2> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let reversed: Vec<Vec2> = points.iter().rev().copied().collect();
+    let mut hull: Vec<Vec2> = Vec::with_capacity(points.len() * 2);
+    for pass in [&points[..], &reversed[..]] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2 {
+                let (a, b) = (hull[hull.len() - 2], hull[hull.len() - 1]);
+                if (b - a).perp_dot(*point - a) > 0.0 {
+                    break;
+                }
+                hull.pop();
+            }
+            hull.push(*point);
+        }
+        // the last point of each half starts the other
+        hull.pop();
+    }
+    hull
+}
@@ -1,7 +1,21 @@
 use bevy::prelude::*;
 
+use super::resources::AssetFailure;
+
 #[derive(Event)]
 pub struct PrepareLevelEvent(pub String);
 
+/// despawns the current level and releases the assets loaded for it
+#[derive(Event)]
+pub struct UnloadLevelEvent;
+
+/// unloads the current level, then prepares the named one
+#[derive(Event)]
+pub struct SwapLevelEvent(pub String);
+
 #[derive(Event)]
 pub struct PostProgresssEvent(pub String, pub u32, pub u32);
+
+/// sent when the current `SessionAssets` can not be loaded, carrying the failures that caused it
+#[derive(Event)]
+pub struct AssetsFailedEvent(pub Vec<AssetFailure>);
@@ -0,0 +1,116 @@
+use std::fmt;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+
+use super::resources::SessionAssets;
+
+/// A [`SessionAssets`] manifest loaded from a `.manifest.ron` or `.manifest.json` file.
+///
+/// ```ron
+/// (
+///     scenes: {
+///         "hub": (path: "levels/hub"),
+///     },
+///     sprite_sheets: {
+///         "hero": (path: "hero", priority: 10),
+///     },
+///     images: {
+///         "splash": (path: "splash", format: Some("jpg"), optional: true),
+///     },
+/// )
+/// ```
+#[derive(Asset, TypePath, Clone, Debug, Deref)]
+pub struct LevelManifest(pub SessionAssets);
+
+#[derive(Default)]
+pub struct ManifestLoader;
+
+/// Error of the loaders reading assets from RON (or JSON) files, manifests and ai profiles among
+/// them. Bevy reports it along with the path of the asset.
+#[derive(Debug)]
+pub enum RonLoaderError {
+    Io(std::io::Error),
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for RonLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RonLoaderError::Io(err) => write!(f, "could not read asset: {}", err),
+            RonLoaderError::Parse {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "invalid asset at line {}, column {}: {}",
+                line, column, message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RonLoaderError {}
+
+impl From<std::io::Error> for RonLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        RonLoaderError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for RonLoaderError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        RonLoaderError::Parse {
+            line: err.position.line,
+            column: err.position.col,
+            message: err.code.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for RonLoaderError {
+    fn from(err: serde_json::Error) -> Self {
+        RonLoaderError::Parse {
+            line: err.line(),
+            column: err.column(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl AssetLoader for ManifestLoader {
+    type Asset = LevelManifest;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let is_json = load_context
+            .path()
+            .extension()
+            .is_some_and(|ext| ext == "json");
+        let assets = if is_json {
+            serde_json::from_slice(&bytes)?
+        } else {
+            ron::de::from_bytes(&bytes)?
+        };
+        Ok(LevelManifest(assets))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["manifest.ron", "manifest.json"]
+    }
+}
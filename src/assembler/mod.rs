@@ -1,17 +1,25 @@
+use crate::resources::HammerspaceConfig;
 use bevy::prelude::*;
 use components::MaterialMarker;
-use events::{PostProgresssEvent, PrepareLevelEvent};
+use events::{
+    AssetsFailedEvent, PostProgresssEvent, PrepareLevelEvent, SwapLevelEvent, UnloadLevelEvent,
+};
 
 use iyes_progress::ProgressPlugin;
+use loader::{LevelManifest, ManifestLoader};
 use resources::{
-    check_assets_ready, init_resources, ImageAssets, ImageAssetsLoading, MeshAssets,
-    PreparedScenes, SessionAssets,
+    check_assets_ready, init_resources, AssetLoadPolicy, AssetLoadReport, AssetsLoading,
+    ChunkRegistry, CurrentLevel, ImageAssets, MeshAssets, PendingManifests, PreparedScenes,
+    SessionAssets,
 };
 
-use systems::setup_blueprints;
+use systems::{
+    process_level_nodes, resolve_level_manifest, setup_blueprints, stream_chunks, unload_level,
+};
 
 pub mod components;
 pub mod events;
+pub mod loader;
 pub mod resources;
 pub mod systems;
 pub struct LoaderPlugin;
@@ -24,22 +32,44 @@ impl Plugin for LoaderPlugin {
                 ProgressPlugin::<AssetLoadState>::new()
                     .with_state_transition(AssetLoadState::Loading, AssetLoadState::Loaded),
             )
+            .init_resource::<HammerspaceConfig>()
             .init_resource::<ImageAssets>()
             .init_resource::<MeshAssets>()
             .init_resource::<PreparedScenes>()
+            .init_resource::<AssetLoadPolicy>()
+            .init_resource::<AssetLoadReport>()
+            .init_resource::<CurrentLevel>()
+            .init_resource::<PendingManifests>()
+            .init_resource::<ChunkRegistry>()
+            .init_asset::<LevelManifest>()
+            .init_asset_loader::<ManifestLoader>()
             .add_event::<PrepareLevelEvent>()
             .add_event::<PostProgresssEvent>()
+            .add_event::<AssetsFailedEvent>()
+            .add_event::<UnloadLevelEvent>()
+            .add_event::<SwapLevelEvent>()
             .add_systems(
                 Update,
                 (
+                    unload_level
+                        .run_if(on_event::<UnloadLevelEvent>.or(on_event::<SwapLevelEvent>)),
                     setup_blueprints.run_if(on_event::<PrepareLevelEvent>),
+                    resolve_level_manifest
+                        .run_if(|pending: Res<PendingManifests>| !pending.0.is_empty()),
                     init_resources.run_if(resource_added::<SessionAssets>),
                     check_assets_ready
-                        .run_if(resource_exists::<ImageAssetsLoading>)
+                        .run_if(resource_exists::<AssetsLoading>)
                         .run_if(in_state(AssetLoadState::Loading)),
                 )
                     .chain(),
             )
+            .add_systems(
+                Update,
+                (
+                    stream_chunks.run_if(in_state(AssetLoadState::Loaded)),
+                    process_level_nodes,
+                ),
+            )
             .register_type::<MaterialMarker>();
     }
 }
@@ -1,19 +1,339 @@
-use super::{events::PostProgresssEvent, AssetLoadState};
-use bevy::{asset::Handle, gltf::Gltf, prelude::*, utils::HashMap};
+use crate::resources::HammerspaceConfig;
+
+use super::{
+    events::{AssetsFailedEvent, PostProgresssEvent},
+    loader::LevelManifest,
+    AssetLoadState,
+};
+use bevy::{
+    asset::{AssetLoadError, Handle, LoadState, RecursiveDependencyLoadState},
+    gltf::Gltf,
+    prelude::*,
+    utils::HashMap,
+};
 use blenvy::GameWorldTag;
 use iyes_progress::ProgressEntry;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Resource)]
 pub struct LoadingTextures(pub Vec<Sprite>);
 
-#[derive(Resource)]
-pub struct SessionAssets(
-    pub HashMap<String, String>,
-    pub HashMap<String, String>,
-    pub HashMap<String, String>,
-    pub HashMap<String, String>,
-);
+/// The kind of asset a [`SessionAssets`] entry describes, which decides where it is loaded from
+/// and which resource the loaded handle ends up in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AssetCategory {
+    /// gltf scenes, stored in `PreparedScenes`
+    Scene,
+    /// folders of sprite sheet layers, stored in [`ImageAssets`] as `<name>/<layer>`
+    SpriteSheet,
+    /// single still images, stored in [`ImageAssets`]
+    Image,
+    /// gltf meshes, stored in [`MeshAssets`]
+    Mesh,
+}
+
+impl AssetCategory {
+    pub const ALL: [AssetCategory; 4] = [
+        AssetCategory::Scene,
+        AssetCategory::SpriteSheet,
+        AssetCategory::Image,
+        AssetCategory::Mesh,
+    ];
+
+    /// file extension used when an entry does not specify its own format
+    pub fn default_format<'a>(&self, config: &'a HammerspaceConfig) -> &'a str {
+        match self {
+            AssetCategory::Scene | AssetCategory::Mesh => &config.mesh_extension,
+            AssetCategory::SpriteSheet | AssetCategory::Image => "png",
+        }
+    }
+
+    /// folder, relative to the asset root, that entries of this category are loaded from
+    pub fn root<'a>(&self, config: &'a HammerspaceConfig) -> &'a str {
+        match self {
+            AssetCategory::Scene | AssetCategory::Mesh => "",
+            AssetCategory::SpriteSheet => &config.sprite_sheet_folder,
+            AssetCategory::Image => &config.image_folder,
+        }
+    }
+}
+
+/// A single asset in a [`SessionAssets`] manifest.
+///
+/// `path` is given without an extension, the extension comes from `format` or falls back to
+/// [`AssetCategory::default_format`]. Entries with a higher `priority` are requested first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetEntry {
+    pub path: String,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub sub_path: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl AssetEntry {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            format: None,
+            sub_path: None,
+            priority: 0,
+            optional: false,
+        }
+    }
+
+    pub fn with_format(mut self, format: impl Into<String>) -> Self {
+        self.format = Some(format.into());
+        self
+    }
+
+    /// folder between the category root and `path`
+    pub fn with_sub_path(mut self, sub_path: impl Into<String>) -> Self {
+        self.sub_path = Some(sub_path.into());
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// optional assets are allowed to be missing without failing the level
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    pub fn format_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.format.as_deref().unwrap_or(default)
+    }
+
+    /// path of the entry without its extension, prefixed with the category root and sub-path
+    pub fn base_path(&self, category: AssetCategory, config: &HammerspaceConfig) -> String {
+        let root = category.root(config);
+        match &self.sub_path {
+            Some(sub_path) => HammerspaceConfig::join(
+                &HammerspaceConfig::join(root, sub_path.trim_end_matches('/')),
+                &self.path,
+            ),
+            None => HammerspaceConfig::join(root, &self.path),
+        }
+    }
+
+    /// full asset path of the entry, sprite sheets resolve to one file per `layer`
+    pub fn asset_path(
+        &self,
+        category: AssetCategory,
+        layer: Option<&str>,
+        config: &HammerspaceConfig,
+    ) -> String {
+        let format = self.format_or(category.default_format(config));
+        let base = self.base_path(category, config);
+        match layer {
+            Some(layer) => format!("{}/{}.{}", base, layer, format),
+            None => format!("{}.{}", base, format),
+        }
+    }
+}
+
+impl From<&str> for AssetEntry {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<String> for AssetEntry {
+    fn from(path: String) -> Self {
+        Self::new(path)
+    }
+}
+
+/// Manifest of every asset a session needs, inserting it kicks off `init_resources`.
+///
+/// ```ignore
+/// commands.insert_resource(
+///     SessionAssets::new()
+///         .with_scene("hub", "levels/hub")
+///         .with_sprite_sheet("hero", AssetEntry::new("hero").with_priority(10))
+///         .with_image("splash", AssetEntry::new("splash").optional()),
+/// );
+/// ```
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionAssets {
+    pub scenes: HashMap<String, AssetEntry>,
+    pub sprite_sheets: HashMap<String, AssetEntry>,
+    pub images: HashMap<String, AssetEntry>,
+    pub meshes: HashMap<String, AssetEntry>,
+}
+
+impl SessionAssets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_scene(self, name: impl Into<String>, entry: impl Into<AssetEntry>) -> Self {
+        self.with_entry(AssetCategory::Scene, name, entry)
+    }
+
+    pub fn with_sprite_sheet(self, name: impl Into<String>, entry: impl Into<AssetEntry>) -> Self {
+        self.with_entry(AssetCategory::SpriteSheet, name, entry)
+    }
+
+    pub fn with_image(self, name: impl Into<String>, entry: impl Into<AssetEntry>) -> Self {
+        self.with_entry(AssetCategory::Image, name, entry)
+    }
+
+    pub fn with_mesh(self, name: impl Into<String>, entry: impl Into<AssetEntry>) -> Self {
+        self.with_entry(AssetCategory::Mesh, name, entry)
+    }
+
+    pub fn with_entry(
+        mut self,
+        category: AssetCategory,
+        name: impl Into<String>,
+        entry: impl Into<AssetEntry>,
+    ) -> Self {
+        self.insert(category, name, entry);
+        self
+    }
+
+    pub fn insert(
+        &mut self,
+        category: AssetCategory,
+        name: impl Into<String>,
+        entry: impl Into<AssetEntry>,
+    ) -> Option<AssetEntry> {
+        self.category_mut(category)
+            .insert(name.into(), entry.into())
+    }
+
+    pub fn category(&self, category: AssetCategory) -> &HashMap<String, AssetEntry> {
+        match category {
+            AssetCategory::Scene => &self.scenes,
+            AssetCategory::SpriteSheet => &self.sprite_sheets,
+            AssetCategory::Image => &self.images,
+            AssetCategory::Mesh => &self.meshes,
+        }
+    }
+
+    pub fn category_mut(&mut self, category: AssetCategory) -> &mut HashMap<String, AssetEntry> {
+        match category {
+            AssetCategory::Scene => &mut self.scenes,
+            AssetCategory::SpriteSheet => &mut self.sprite_sheets,
+            AssetCategory::Image => &mut self.images,
+            AssetCategory::Mesh => &mut self.meshes,
+        }
+    }
+
+    /// entries of a category, highest priority first
+    pub fn by_priority(&self, category: AssetCategory) -> Vec<(&String, &AssetEntry)> {
+        let mut entries: Vec<_> = self.category(category).iter().collect();
+        entries.sort_by(|a, b| b.1.priority.cmp(&a.1.priority).then_with(|| a.0.cmp(b.0)));
+        entries
+    }
+
+    /// adds the entries of `other`, which win over entries of the same name
+    pub fn merge(&mut self, mut other: SessionAssets) {
+        for category in AssetCategory::ALL {
+            let entries = std::mem::take(other.category_mut(category));
+            self.category_mut(category).extend(entries);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        AssetCategory::ALL
+            .iter()
+            .map(|category| self.category(*category).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A sub-level blueprint that is streamed in around `position` while the `Player` is near it.
+///
+/// Chunks spawn once the player is within `load_radius` and despawn once they are further than
+/// `unload_radius`, the gap between the two keeps chunks from flickering at the border.
+#[derive(Clone, Debug)]
+pub struct ChunkDef {
+    pub name: String,
+    pub path: String,
+    pub position: Vec3,
+    pub load_radius: f32,
+    pub unload_radius: f32,
+}
 
+impl ChunkDef {
+    pub fn new(name: impl Into<String>, path: impl Into<String>, position: Vec3) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            position,
+            load_radius: 50.0,
+            unload_radius: 60.0,
+        }
+    }
+
+    pub fn with_radius(mut self, load_radius: f32, unload_radius: f32) -> Self {
+        self.load_radius = load_radius;
+        self.unload_radius = unload_radius.max(load_radius);
+        self
+    }
+}
+
+/// sub-levels streamed additively next to the main game world, cleared when the level is unloaded
+/// so chunks are registered again for each level
+#[derive(Resource, Clone, Debug)]
+pub struct ChunkRegistry {
+    pub chunks: Vec<ChunkDef>,
+    /// how many chunks may be spawned in a single frame
+    pub spawn_budget: usize,
+    pub(crate) spawned: HashMap<String, Entity>,
+}
+
+impl Default for ChunkRegistry {
+    fn default() -> Self {
+        Self {
+            chunks: Vec::new(),
+            spawn_budget: 1,
+            spawned: HashMap::default(),
+        }
+    }
+}
+
+impl ChunkRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_chunk(mut self, chunk: ChunkDef) -> Self {
+        self.chunks.push(chunk);
+        self
+    }
+
+    pub fn with_spawn_budget(mut self, spawn_budget: usize) -> Self {
+        self.spawn_budget = spawn_budget;
+        self
+    }
+
+    pub fn is_spawned(&self, name: &str) -> bool {
+        self.spawned.contains_key(name)
+    }
+}
+
+/// name of the level that was last prepared, `None` once it has been unloaded
+#[derive(Resource, Default, Debug, Clone, PartialEq, Eq)]
+pub struct CurrentLevel(pub Option<String>);
+
+/// loaded images by name, sprite sheet layers are keyed `<sheet>/<layer>`, like `hero/uv_color`
 #[derive(Resource, Default)]
 pub struct ImageAssets(pub HashMap<String, Sprite>);
 
@@ -23,133 +343,301 @@ pub struct MeshAssets(pub HashMap<String, Handle<Gltf>>);
 #[derive(Resource, Default)]
 pub(crate) struct PreparedScenes(pub HashMap<String, Handle<Gltf>>);
 
+/// what to do when an asset requested by `init_resources` fails to load
+#[derive(Resource, Clone, Debug)]
+pub struct AssetLoadPolicy {
+    /// enter `AssetLoadState::Failed` on the first fatal failure instead of waiting for
+    /// every other asset to settle
+    pub fail_fast: bool,
+    /// failures of entries marked `optional` are reported but do not fail the level
+    pub tolerate_optional: bool,
+    /// how many times a failed asset is reloaded before it counts as failed
+    pub max_retries: u32,
+    /// seconds to wait between retries
+    pub retry_delay: f32,
+}
+
+impl Default for AssetLoadPolicy {
+    fn default() -> Self {
+        Self {
+            fail_fast: true,
+            tolerate_optional: true,
+            max_retries: 0,
+            retry_delay: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AssetFailure {
+    pub path: String,
+    pub category: AssetCategory,
+    pub optional: bool,
+    pub error: String,
+}
+
+/// every asset that failed while loading the current `SessionAssets`
+#[derive(Resource, Clone, Debug, Default)]
+pub struct AssetLoadReport {
+    pub failures: Vec<AssetFailure>,
+}
+
+impl AssetLoadReport {
+    /// failures that prevent the level from loading under the given policy
+    pub fn fatal<'a>(
+        &'a self,
+        policy: &'a AssetLoadPolicy,
+    ) -> impl Iterator<Item = &'a AssetFailure> {
+        self.failures
+            .iter()
+            .filter(|failure| !(failure.optional && policy.tolerate_optional))
+    }
+}
+
+/// an asset requested by `init_resources` that `check_assets_ready` is waiting on
+pub(crate) struct TrackedAsset {
+    pub path: String,
+    pub category: AssetCategory,
+    pub optional: bool,
+    pub handle: UntypedHandle,
+    pub attempts: u32,
+    pub retry_at: f64,
+    /// failure a reload was asked for, still reported until the reload gets going
+    pub reloading: Option<Arc<AssetLoadError>>,
+    pub loaded: bool,
+    pub failed: bool,
+}
+
+impl TrackedAsset {
+    fn new(
+        path: String,
+        category: AssetCategory,
+        entry: &AssetEntry,
+        handle: UntypedHandle,
+    ) -> Self {
+        Self {
+            path,
+            category,
+            optional: entry.optional,
+            handle,
+            attempts: 0,
+            retry_at: 0.0,
+            reloading: None,
+            loaded: false,
+            failed: false,
+        }
+    }
+
+    fn settled(&self) -> bool {
+        self.loaded || self.failed
+    }
+
+    /// `None` while the asset or any of its dependencies are still loading
+    fn load_result(&self, server: &AssetServer) -> Option<Result<(), Arc<AssetLoadError>>> {
+        if let LoadState::Failed(err) = server.load_state(&self.handle) {
+            return Some(Err(err));
+        }
+        match server.recursive_dependency_load_state(&self.handle) {
+            RecursiveDependencyLoadState::Loaded => Some(Ok(())),
+            RecursiveDependencyLoadState::Failed(err) => Some(Err(err)),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Resource, Default)]
-pub(crate) struct ImageAssetsLoading(pub Vec<Sprite>);
+pub(crate) struct AssetsLoading(pub Vec<TrackedAsset>);
 
+/// manifests requested for the levels being prepared, resolved in the order they were asked for
 #[derive(Resource, Default)]
-pub(crate) struct MeshAssetsLoading(pub Vec<Handle<Gltf>>);
+pub(crate) struct PendingManifests(pub Vec<PendingManifest>);
 
+/// manifest requested for a level, with the paths still left to try if it does not exist
+pub(crate) struct PendingManifest {
+    pub level: String,
+    pub handle: Handle<LevelManifest>,
+    pub fallbacks: Vec<String>,
+}
+
+/// starts loading `SessionAssets`, adding to the assets already loading when several levels are
+/// prepared together
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn init_resources(
     mut commands: Commands,
     session_assets: Res<SessionAssets>,
+    tracked: Option<ResMut<AssetsLoading>>,
     mut mesh_assets: ResMut<MeshAssets>,
     mut image_assets: ResMut<ImageAssets>,
     mut scenes: ResMut<PreparedScenes>,
     server: Res<AssetServer>,
+    config: Res<HammerspaceConfig>,
 ) {
     info!("initializing resources");
-    info!("initializing sprites");
+    let mut loading = Vec::new();
+
     //scene
-    scenes.0.extend(
-        session_assets
-            .0
-            .iter()
-            .map(|f| (f.0.to_string(), server.load(f.1.to_string() + ".gltf"))),
-    );
-
-    for map in [
-        ["uv_color", "uv_canvas"],
-        ["occlusion", "occlusion"],
-        ["normals", "normal_sheet"],
-        ["mask", "uv_sheet"],
-        ["volume", "volume"],
-    ] {
-        //sprite_sheets
-        image_assets.0.extend(session_assets.1.iter().map(|f| {
-            (
-                map[0].to_string(),
-                Sprite {
-                    image: server.load("images/sprites/".to_owned() + f.1 + "/" + map[1] + ".png"),
-                    ..default()
-                },
-            )
-        }));
+    for (name, entry) in session_assets.by_priority(AssetCategory::Scene) {
+        let path = entry.asset_path(AssetCategory::Scene, None, &config);
+        let handle: Handle<Gltf> = server.load(&path);
+        loading.push(TrackedAsset::new(
+            path,
+            AssetCategory::Scene,
+            entry,
+            handle.clone().untyped(),
+        ));
+        scenes.0.insert(name.to_string(), handle);
     }
-    info!("initializing images");
 
+    info!("initializing sprites");
+    //sprite_sheets
+    for (name, entry) in session_assets.by_priority(AssetCategory::SpriteSheet) {
+        for map in [
+            ["uv_color", "uv_canvas"],
+            ["occlusion", "occlusion"],
+            ["normals", "normal_sheet"],
+            ["mask", "uv_sheet"],
+            ["volume", "volume"],
+        ] {
+            let path = entry.asset_path(AssetCategory::SpriteSheet, Some(map[1]), &config);
+            let image: Handle<Image> = server.load(&path);
+            loading.push(TrackedAsset::new(
+                path,
+                AssetCategory::SpriteSheet,
+                entry,
+                image.clone().untyped(),
+            ));
+            image_assets.0.insert(
+                format!("{}/{}", name, map[0]),
+                Sprite { image, ..default() },
+            );
+        }
+    }
+
+    info!("initializing images");
     //still images
-    image_assets.0.extend(session_assets.2.iter().map(|f| {
-        (
-            f.0.to_string(),
-            Sprite {
-                image: server.load("images/".to_owned() + f.1 + ".png"),
-                ..default()
-            },
-        )
-    }));
+    for (name, entry) in session_assets.by_priority(AssetCategory::Image) {
+        let path = entry.asset_path(AssetCategory::Image, None, &config);
+        let image: Handle<Image> = server.load(&path);
+        loading.push(TrackedAsset::new(
+            path,
+            AssetCategory::Image,
+            entry,
+            image.clone().untyped(),
+        ));
+        image_assets
+            .0
+            .insert(name.to_string(), Sprite { image, ..default() });
+    }
 
     //meshes
-    mesh_assets.0.extend(
-        session_assets
-            .3
-            .iter()
-            .map(|f| (f.0.to_string(), server.load(f.1.to_string() + ".gltf"))),
-    );
-
-    let mut loading_images = Vec::new();
-    let mut loading_meshes = Vec::new();
-
-    for image in &image_assets.0 {
-        loading_images.push(image.1.clone())
+    for (name, entry) in session_assets.by_priority(AssetCategory::Mesh) {
+        let path = entry.asset_path(AssetCategory::Mesh, None, &config);
+        let handle: Handle<Gltf> = server.load(&path);
+        loading.push(TrackedAsset::new(
+            path,
+            AssetCategory::Mesh,
+            entry,
+            handle.clone().untyped(),
+        ));
+        mesh_assets.0.insert(name.to_string(), handle);
     }
 
-    for mesh in &mesh_assets.0 {
-        loading_meshes.push(mesh.1.clone());
+    match tracked {
+        Some(mut tracked) => {
+            loading.retain(|asset| !tracked.0.iter().any(|other| other.path == asset.path));
+            tracked.0.extend(loading);
+        }
+        None => {
+            commands.insert_resource(AssetLoadReport::default());
+            commands.insert_resource(AssetsLoading(loading));
+        }
     }
-    commands.insert_resource(ImageAssetsLoading(loading_images.clone()));
-    commands.insert_resource(MeshAssetsLoading(loading_meshes.clone()));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn check_assets_ready(
     world: Query<&GameWorldTag>,
     progress: ProgressEntry<AssetLoadState>,
     mut progress_ev: EventWriter<PostProgresssEvent>,
+    mut failed_ev: EventWriter<AssetsFailedEvent>,
+    mut next_state: ResMut<NextState<AssetLoadState>>,
     mut initted: Local<bool>,
     server: Res<AssetServer>,
-    image_assets_loading: Res<ImageAssetsLoading>,
-    mesh_assets_loading: Res<MeshAssetsLoading>,
+    time: Res<Time>,
+    policy: Res<AssetLoadPolicy>,
+    mut report: ResMut<AssetLoadReport>,
+    mut loading: ResMut<AssetsLoading>,
 ) {
-    if world.get_single().is_ok() {
+    if loading.is_added() {
+        *initted = false;
+    }
+    // levels prepared together each spawn their own world
+    if !world.is_empty() {
         info!("checking assets");
+        let total = loading.0.len() as u32;
         if !*initted {
-            progress.set_total(
-                image_assets_loading.0.len() as u32 + mesh_assets_loading.0.len() as u32,
-            );
+            progress.set_progress(0, total);
             *initted = true;
+        } else if progress.get_total() < total {
+            // assets of a level prepared since count towards the total, keeping what is done
+            progress.set_progress(progress.get_done(), total);
         }
-        for sprite in &image_assets_loading.0 {
-            match server.get_load_state(&sprite.image.clone()).unwrap() {
-                bevy::asset::LoadState::Failed(err) => {
-                    error!("Image failed to load: {:?}", err);
+        let now = time.elapsed_secs_f64();
+        let mut new_fatal = false;
+        for asset in loading.0.iter_mut().filter(|asset| !asset.settled()) {
+            match asset.load_result(&server) {
+                Some(Err(err)) => {
+                    let stale = asset
+                        .reloading
+                        .as_ref()
+                        .is_some_and(|reloading| Arc::ptr_eq(reloading, &err));
+                    if stale || now < asset.retry_at {
+                        continue;
+                    }
+                    if asset.attempts < policy.max_retries {
+                        asset.attempts += 1;
+                        asset.retry_at = now + policy.retry_delay as f64;
+                        warn!(
+                            "Retrying {} ({}/{}): {}",
+                            asset.path, asset.attempts, policy.max_retries, err
+                        );
+                        asset.reloading = Some(err);
+                        server.reload(asset.path.clone());
+                        continue;
+                    }
+                    error!("{:?} failed to load: {}", asset.category, err);
+                    asset.failed = true;
+                    report.failures.push(AssetFailure {
+                        path: asset.path.clone(),
+                        category: asset.category,
+                        optional: asset.optional,
+                        error: err.to_string(),
+                    });
+                    if !(asset.optional && policy.tolerate_optional) {
+                        new_fatal = true;
+                        continue;
+                    }
                 }
-                bevy::asset::LoadState::Loaded => {
-                    progress.add_done(1);
-                    progress_ev.send(PostProgresssEvent(
-                        "loading images".to_string(),
-                        progress.get_done(),
-                        progress.get_total(),
-                    ));
+                Some(Ok(())) => asset.loaded = true,
+                None => {
+                    asset.reloading = None;
+                    continue;
                 }
-                _ => {}
             }
+            progress.add_done(1);
+            progress_ev.send(PostProgresssEvent(
+                format!("loading {:?}", asset.category).to_lowercase(),
+                progress.get_done(),
+                progress.get_total(),
+            ));
         }
-        info!("checking meshes");
 
-        for mesh_and_scene in &mesh_assets_loading.0 {
-            match server.get_load_state(&mesh_and_scene.clone()).unwrap() {
-                bevy::asset::LoadState::Failed(err) => {
-                    error!("Mesh failed to load: {:?}", err);
-                }
-                bevy::asset::LoadState::Loaded => {
-                    progress.add_done(1);
-                    progress_ev.send(PostProgresssEvent(
-                        "loading meshes".to_string(),
-                        progress.get_done(),
-                        progress.get_total(),
-                    ));
-                }
-                _ => {}
-            }
+        let has_fatal = report.fatal(&policy).next().is_some();
+        let settled = loading.0.iter().all(TrackedAsset::settled);
+        if (new_fatal && policy.fail_fast) || (has_fatal && settled) {
+            failed_ev.send(AssetsFailedEvent(report.fatal(&policy).cloned().collect()));
+            next_state.set(AssetLoadState::Failed);
+            *initted = false;
         }
     }
 }
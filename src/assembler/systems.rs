@@ -1,23 +1,287 @@
-use bevy::prelude::*;
+use bevy::{
+    asset::{io::AssetReaderError, AssetLoadError, LoadState},
+    prelude::*,
+    render::mesh::VertexAttributeValues,
+    utils::HashSet,
+};
 use blenvy::{
-    AddToGameWorld, BlueprintInfo, Dynamic, GameWorldTag, HideUntilReady, SpawnBlueprint,
+    AddToGameWorld, BlueprintInfo, BlueprintInstanceReady, Dynamic, GameWorldTag, HideUntilReady,
+    SpawnBlueprint,
 };
 use rand::Rng;
 
-use super::events::PrepareLevelEvent;
+use crate::{
+    interact::components::Player, location_marker::components::LocationMarker,
+    resources::HammerspaceConfig,
+};
+
+use super::{
+    components::{CollisionShape, LevelChunk, LevelScoped},
+    events::{PrepareLevelEvent, SwapLevelEvent, UnloadLevelEvent},
+    loader::LevelManifest,
+    resources::{
+        AssetLoadReport, AssetsLoading, ChunkDef, ChunkRegistry, CurrentLevel, ImageAssets,
+        MeshAssets, PendingManifest, PendingManifests, PreparedScenes, SessionAssets,
+    },
+    AssetLoadState,
+};
 
-pub fn setup_blueprints(mut level_ev: EventReader<PrepareLevelEvent>, mut commands: Commands) {
+pub fn setup_blueprints(
+    mut level_ev: EventReader<PrepareLevelEvent>,
+    mut commands: Commands,
+    mut current: ResMut<CurrentLevel>,
+    mut next_state: ResMut<NextState<AssetLoadState>>,
+    mut pending: ResMut<PendingManifests>,
+    server: Res<AssetServer>,
+    config: Res<HammerspaceConfig>,
+) {
     for ev in level_ev.read() {
+        current.0 = Some(ev.0.clone());
+        next_state.set(AssetLoadState::Loading);
         commands.spawn((
-            BlueprintInfo::from_path(format!("levels/{}.glb", ev.0).as_str()),
+            BlueprintInfo::from_path(&config.level_path(&ev.0)),
             SpawnBlueprint,
             HideUntilReady,
             GameWorldTag,
         ));
+        let mut manifests = config.level_manifest_paths(&ev.0);
+        pending.0.push(PendingManifest {
+            level: ev.0.clone(),
+            handle: server.load(manifests.remove(0)),
+            fallbacks: manifests,
+        });
+    }
+}
+
+/// tears down the current level, swaps also queue the next level to be prepared
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn unload_level(
+    mut commands: Commands,
+    mut unload_ev: EventReader<UnloadLevelEvent>,
+    mut swap_ev: EventReader<SwapLevelEvent>,
+    mut level_ev: EventWriter<PrepareLevelEvent>,
+    mut next_state: ResMut<NextState<AssetLoadState>>,
+    mut current: ResMut<CurrentLevel>,
+    mut scenes: ResMut<PreparedScenes>,
+    mut mesh_assets: ResMut<MeshAssets>,
+    mut image_assets: ResMut<ImageAssets>,
+    mut pending: ResMut<PendingManifests>,
+    mut registry: ResMut<ChunkRegistry>,
+    level_q: Query<Entity, Or<(With<GameWorldTag>, With<LevelScoped>)>>,
+) {
+    let unload = unload_ev.read().count() > 0;
+    let next = swap_ev.read().last().map(|ev| ev.0.clone());
+    if !unload && next.is_none() {
+        return;
+    }
+
+    if let Some(level) = current.0.take() {
+        info!("unloading level {}", level);
+    }
+    for entity in level_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    scenes.0.clear();
+    mesh_assets.0.clear();
+    image_assets.0.clear();
+    commands.remove_resource::<SessionAssets>();
+    commands.remove_resource::<AssetsLoading>();
+    pending.0.clear();
+    // chunks belong to the level they were registered for, they are despawned with it
+    registry.chunks.clear();
+    registry.spawned.clear();
+    commands.insert_resource(AssetLoadReport::default());
+    next_state.set(AssetLoadState::Initializing);
+
+    if let Some(next) = next {
+        level_ev.send(PrepareLevelEvent(next));
+    }
+}
+
+/// turns the manifests shipped next to levels into their `SessionAssets`, levels without a
+/// manifest are left for game code to provide assets for
+pub(crate) fn resolve_level_manifest(
+    mut commands: Commands,
+    mut pending: ResMut<PendingManifests>,
+    session_assets: Option<Res<SessionAssets>>,
+    manifests: Res<Assets<LevelManifest>>,
+    server: Res<AssetServer>,
+) {
+    let mut resolved: Vec<SessionAssets> = Vec::new();
+    pending
+        .0
+        .retain_mut(|pending| match server.load_state(&pending.handle) {
+            LoadState::Loaded => {
+                if let Some(manifest) = manifests.get(&pending.handle) {
+                    info!("using asset manifest for level {}", pending.level);
+                    resolved.push(manifest.0.clone());
+                }
+                false
+            }
+            LoadState::Failed(err) => {
+                if let AssetLoadError::AssetReaderError(AssetReaderError::NotFound(_)) = *err {
+                    if !pending.fallbacks.is_empty() {
+                        let next = pending.fallbacks.remove(0);
+                        pending.handle = server.load(next);
+                        return true;
+                    }
+                    info!("level {} has no asset manifest", pending.level);
+                } else {
+                    error!(
+                        "Manifest for level {} failed to load: {}",
+                        pending.level, err
+                    );
+                }
+                false
+            }
+            _ => true,
+        });
+    if resolved.is_empty() {
+        return;
+    }
+
+    // levels prepared together share their assets, re-adding the manifest loads the new ones
+    // next to those already loading
+    let mut merged = session_assets
+        .map(|assets| assets.clone())
+        .unwrap_or_default();
+    for assets in resolved {
+        merged.merge(assets);
+    }
+    commands.remove_resource::<SessionAssets>();
+    commands.insert_resource(merged);
+}
+
+/// spawns and despawns registered chunks around the player, nearest chunks first
+pub(crate) fn stream_chunks(
+    mut commands: Commands,
+    mut registry: ResMut<ChunkRegistry>,
+    player_q: Query<&GlobalTransform, With<Player>>,
+    chunk_q: Query<(), With<LevelChunk>>,
+) {
+    let Ok(player) = player_q.get_single() else {
+        return;
+    };
+    let position = player.translation();
+    let registry = registry.as_mut();
+
+    // chunks torn down with their level are forgotten
+    registry
+        .spawned
+        .retain(|_, entity| chunk_q.contains(*entity));
+
+    for chunk in &registry.chunks {
+        let distance = chunk.position.distance(position);
+        if distance > chunk.unload_radius {
+            if let Some(entity) = registry.spawned.remove(&chunk.name) {
+                info!("despawning chunk {}", chunk.name);
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+
+    let mut pending: Vec<(f32, &ChunkDef)> = registry
+        .chunks
+        .iter()
+        .filter(|chunk| !registry.spawned.contains_key(&chunk.name))
+        .map(|chunk| (chunk.position.distance(position), chunk))
+        .filter(|(distance, chunk)| *distance <= chunk.load_radius)
+        .collect();
+    pending.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for (_, chunk) in pending.into_iter().take(registry.spawn_budget) {
+        info!("spawning chunk {}", chunk.name);
+        let entity = commands
+            .spawn((
+                BlueprintInfo::from_path(&chunk.path),
+                SpawnBlueprint,
+                HideUntilReady,
+                LevelChunk(chunk.name.clone()),
+                Name::from(chunk.name.clone()),
+                Transform::from_translation(chunk.position),
+            ))
+            .id();
+        registry.spawned.insert(chunk.name.clone(), entity);
+    }
+}
+
+/// converts level nodes named with the configured light, collision and spawn identifiers once
+/// a level or chunk has finished spawning
+pub(crate) fn process_level_nodes(
+    mut commands: Commands,
+    ready_q: Query<
+        Entity,
+        (
+            Added<BlueprintInstanceReady>,
+            Or<(With<GameWorldTag>, With<LevelChunk>)>,
+        ),
+    >,
+    children_q: Query<&Children>,
+    node_q: Query<(&Name, Option<&Mesh3d>)>,
+    meshes: Res<Assets<Mesh>>,
+    config: Res<HammerspaceConfig>,
+) {
+    for root in ready_q.iter() {
+        for node in children_q.iter_descendants(root) {
+            let Ok((name, _)) = node_q.get(node) else {
+                continue;
+            };
+            if HammerspaceConfig::has_identifier(name, &config.lights_identifier) {
+                commands.entity(node).insert(config.level_light.clone());
+            } else if HammerspaceConfig::has_identifier(name, &config.collision_identifier) {
+                let points = collider_points(node, &children_q, &node_q, &meshes);
+                let mut entity = commands.entity(node);
+                entity.insert(Visibility::Hidden);
+                match CollisionShape::from_points(&points, config.convex_colliders) {
+                    Some(shape) => {
+                        entity.insert(shape);
+                    }
+                    None => warn!("collider {} has no mesh to derive a shape from", name),
+                }
+            } else if HammerspaceConfig::has_identifier(name, &config.spawn_identifier) {
+                commands.entity(node).insert(LocationMarker);
+            }
+        }
     }
 }
 
-pub fn spawn_actor<'a>(commands: &'a mut Commands, name: String) -> EntityCommands<'a> {
+/// vertex positions of the meshes on a node and its direct children (the gltf primitives)
+fn collider_points(
+    node: Entity,
+    children_q: &Query<&Children>,
+    node_q: &Query<(&Name, Option<&Mesh3d>)>,
+    meshes: &Assets<Mesh>,
+) -> Vec<Vec3> {
+    let primitives = children_q
+        .get(node)
+        .map(|children| children.to_vec())
+        .unwrap_or_default();
+    let mut seen = HashSet::new();
+    let mut points: Vec<Vec3> = Vec::new();
+    for entity in std::iter::once(node).chain(primitives) {
+        let Ok((_, Some(mesh))) = node_q.get(entity) else {
+            continue;
+        };
+        let Some(VertexAttributeValues::Float32x3(positions)) = meshes
+            .get(&mesh.0)
+            .and_then(|mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION))
+        else {
+            continue;
+        };
+        for position in positions {
+            if seen.insert(position.map(f32::to_bits)) {
+                points.push(Vec3::from_array(*position));
+            }
+        }
+    }
+    points
+}
+
+pub fn spawn_actor<'a>(
+    commands: &'a mut Commands,
+    config: &HammerspaceConfig,
+    name: String,
+) -> EntityCommands<'a> {
     let mut rng = rand::thread_rng();
     let range = 1.5;
     let x: f32 = rng.gen_range(-range..range);
@@ -28,7 +292,7 @@ pub fn spawn_actor<'a>(commands: &'a mut Commands, name: String) -> EntityComman
     commands.spawn((
         BlueprintInfo {
             name: name.clone(),
-            path: format!("blueprints/{}.glb", name),
+            path: config.blueprint_path(&name),
         },
         Dynamic,
         Name::from(format!("test {}", name_index)),
@@ -37,4 +301,3 @@ pub fn spawn_actor<'a>(commands: &'a mut Commands, name: String) -> EntityComman
         Transform::from_xyz(x, 2.0, y),
     ))
 }
-
@@ -1,4 +1,5 @@
 use crate::location_marker::LocationMarkerPlugin;
+use ai_controller::AiControllerPlugin;
 use assembler::LoaderPlugin;
 use bevy::prelude::*;
 use blenvy::BlenvyPlugin;
@@ -7,6 +8,8 @@ use interact::InteractPlugin;
 #[cfg(feature = "pathfind")]
 use pathfind::{events::PathEvent, PathFindPlugin};
 use resources::HammerspaceConfig;
+#[cfg(feature = "proc_terrain")]
+use terrain::TerrainPlugin;
 
 pub mod ai_controller;
 pub mod assembler;
@@ -26,10 +29,15 @@ pub struct HammerspacePlugin {
 
 impl Plugin for HammerspacePlugin {
     fn build(&self, app: &mut App) {
+        if let Err(err) = self.config.validate() {
+            panic!("invalid HammerspaceConfig: {}", err);
+        }
+        app.insert_resource::<HammerspaceConfig>(self.config.clone());
         app.add_plugins((
             LoaderPlugin,
             LocationMarkerPlugin,
             InteractPlugin,
+            AiControllerPlugin,
             BlenvyPlugin::default(),
             #[cfg(feature = "pathfind")]
             PathFindPlugin,
@@ -38,6 +46,5 @@ impl Plugin for HammerspacePlugin {
         ));
         #[cfg(feature = "pathfind")]
         app.add_event::<PathEvent>();
-        app.insert_resource::<HammerspaceConfig>(self.config.clone());
     }
 }
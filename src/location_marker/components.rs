@@ -1,5 +1,29 @@
 use bevy::prelude::*;
 
-#[derive(Component, Reflect)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 #[require(Name, Transform)]
 pub struct LocationMarker;
+
+/// Sends the `Player` to `marker` in `level` when they walk into it.
+///
+/// Overlap uses the `CollisionShape` of the portal when it has one, otherwise `radius` around
+/// its origin.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct Portal {
+    pub level: String,
+    pub marker: String,
+    pub radius: f32,
+}
+
+impl Default for Portal {
+    fn default() -> Self {
+        Self {
+            level: String::new(),
+            marker: String::new(),
+            radius: 1.5,
+        }
+    }
+}
@@ -1,4 +1,29 @@
 use bevy::prelude::*;
 
+/// moves the entity to the named `LocationMarker`, or spawns the player blueprint there when no
+/// entity is given
 #[derive(Event)]
 pub struct LocationSpawnEvent(pub Name, pub Option<Entity>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocationSpawnError {
+    /// no marker with that name exists in the current level
+    Unknown,
+    /// more than one marker has that name
+    Ambiguous(Vec<Entity>),
+    /// the entity to move, or the marker to move it to, no longer exists
+    MissingEntity,
+}
+
+/// outcome of a `LocationSpawnEvent`, carrying the moved or spawned entity on success
+#[derive(Event, Debug)]
+pub struct LocationSpawnResultEvent(pub Name, pub Result<Entity, LocationSpawnError>);
+
+/// Hooks for the screen transition around a portal level swap, the level is swapped once the fade
+/// out has finished and the fade in starts once the player has been placed.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum LevelTransitionEvent {
+    FadeOut { level: String, duration: f32 },
+    FadeIn { level: String, duration: f32 },
+    Finished { level: String },
+}
@@ -1,15 +1,37 @@
+use crate::{assembler::systems::process_level_nodes, resources::HammerspaceConfig};
 use bevy::prelude::*;
-use events::LocationSpawnEvent;
-use systems::send_to_marker;
+use components::{LocationMarker, Portal};
+use events::{LevelTransitionEvent, LocationSpawnEvent, LocationSpawnResultEvent};
+use resources::{LevelTransition, LocationMarkers, TransitionSettings};
+use systems::{advance_transition, enter_portals, register_markers, send_to_marker};
 
 pub mod components;
 pub mod events;
+pub mod resources;
 pub mod systems;
 
 pub struct LocationMarkerPlugin;
 impl Plugin for LocationMarkerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<LocationSpawnEvent>()
-            .add_systems(Update, send_to_marker);
+            .add_event::<LocationSpawnResultEvent>()
+            .add_event::<LevelTransitionEvent>()
+            .init_resource::<HammerspaceConfig>()
+            .init_resource::<LocationMarkers>()
+            .init_resource::<TransitionSettings>()
+            .init_resource::<LevelTransition>()
+            .add_systems(
+                Update,
+                (
+                    register_markers,
+                    send_to_marker,
+                    enter_portals,
+                    advance_transition,
+                )
+                    .chain()
+                    .after(process_level_nodes),
+            )
+            .register_type::<LocationMarker>()
+            .register_type::<Portal>();
     }
 }
@@ -0,0 +1,49 @@
+use bevy::{prelude::*, utils::HashMap};
+
+/// every `LocationMarker` in the world, keyed by its name without the spawn identifier
+#[derive(Resource, Default)]
+pub struct LocationMarkers(pub HashMap<String, Vec<Entity>>);
+
+impl LocationMarkers {
+    pub fn get(&self, name: &str) -> &[Entity] {
+        self.0.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct TransitionSettings {
+    /// seconds spent fading out before the swap and fading in after it
+    pub fade_duration: f32,
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        Self { fade_duration: 0.5 }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum TransitionPhase {
+    FadeOut(Timer),
+    /// waiting for the swap to start, holding the player to move if it outlives the level
+    Swapping(Option<Entity>),
+    Loading,
+    FadeIn(Timer),
+}
+
+/// the portal swap in progress, if any
+#[derive(Resource, Default)]
+pub struct LevelTransition(pub(crate) Option<(String, String, TransitionPhase)>);
+
+impl LevelTransition {
+    pub fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// level and marker the player is being sent to
+    pub fn target(&self) -> Option<(&str, &str)> {
+        self.0
+            .as_ref()
+            .map(|(level, marker, _)| (level.as_str(), marker.as_str()))
+    }
+}
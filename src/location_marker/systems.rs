@@ -1,3 +1,229 @@
-// use bevy::prelude::*;
+use bevy::prelude::*;
+use blenvy::{
+    AddToGameWorld, BlueprintInfo, BlueprintInstanceReady, GameWorldTag, HideUntilReady,
+    SpawnBlueprint,
+};
 
-pub fn send_to_marker() {}
+use crate::{
+    assembler::{
+        components::CollisionShape, events::SwapLevelEvent, resources::CurrentLevel, AssetLoadState,
+    },
+    interact::components::Player,
+    resources::HammerspaceConfig,
+};
+
+use super::{
+    components::{LocationMarker, Portal},
+    events::{
+        LevelTransitionEvent, LocationSpawnError, LocationSpawnEvent, LocationSpawnResultEvent,
+    },
+    resources::{LevelTransition, LocationMarkers, TransitionPhase, TransitionSettings},
+};
+
+/// Places entities at the marker named by each `LocationSpawnEvent`.
+///
+/// Requests are held back while a level is still loading, so a spawn can be sent in the same
+/// frame as the level it targets.
+#[allow(clippy::too_many_arguments)]
+pub fn send_to_marker(
+    mut commands: Commands,
+    mut spawn_ev: EventReader<LocationSpawnEvent>,
+    mut result_ev: EventWriter<LocationSpawnResultEvent>,
+    mut pending: Local<Vec<(Name, Option<Entity>)>>,
+    markers: Res<LocationMarkers>,
+    marker_q: Query<&GlobalTransform, With<LocationMarker>>,
+    parent_q: Query<&Parent>,
+    global_q: Query<&GlobalTransform>,
+    world_q: Query<Has<BlueprintInstanceReady>, With<GameWorldTag>>,
+    load_state: Option<Res<State<AssetLoadState>>>,
+    config: Res<HammerspaceConfig>,
+) {
+    pending.extend(spawn_ev.read().map(|ev| (ev.0.clone(), ev.1)));
+    let loading = load_state.is_some_and(|state| *state.get() == AssetLoadState::Loading);
+    if pending.is_empty() || loading || world_q.iter().any(|ready| !ready) {
+        return;
+    }
+
+    for (name, entity) in pending.drain(..) {
+        let key = HammerspaceConfig::strip_identifier(name.as_str(), &config.spawn_identifier);
+        let result = match markers.get(key) {
+            [] => Err(LocationSpawnError::Unknown),
+            [marker] => match marker_q.get(*marker).copied() {
+                // the marker was despawned without being unregistered yet
+                Err(_) => Err(LocationSpawnError::MissingEntity),
+                Ok(target) => match entity {
+                    Some(entity) => {
+                        // markers are placed in world space, entities are moved in their parent's space
+                        let parent = parent_q
+                            .get(entity)
+                            .ok()
+                            .and_then(|parent| global_q.get(parent.get()).ok());
+                        let transform = match parent {
+                            Some(parent) => target.reparented_to(parent),
+                            None => target.compute_transform(),
+                        };
+                        match commands.get_entity(entity) {
+                            Some(mut entity_commands) => {
+                                entity_commands.insert(transform);
+                                Ok(entity)
+                            }
+                            None => Err(LocationSpawnError::MissingEntity),
+                        }
+                    }
+                    None => Ok(commands
+                        .spawn((
+                            BlueprintInfo::from_path(
+                                &config.blueprint_path(&config.player_blueprint),
+                            ),
+                            SpawnBlueprint,
+                            HideUntilReady,
+                            AddToGameWorld,
+                            Player,
+                            target.compute_transform(),
+                        ))
+                        .id()),
+                },
+            },
+            ambiguous => Err(LocationSpawnError::Ambiguous(ambiguous.to_vec())),
+        };
+        if let Err(err) = &result {
+            warn!("could not send to marker {}: {:?}", name, err);
+        }
+        result_ev.send(LocationSpawnResultEvent(name, result));
+    }
+}
+
+pub(crate) fn register_markers(
+    mut markers: ResMut<LocationMarkers>,
+    mut removed: RemovedComponents<LocationMarker>,
+    added_q: Query<(Entity, &Name), Added<LocationMarker>>,
+    config: Res<HammerspaceConfig>,
+) {
+    for entity in removed.read() {
+        markers.0.retain(|_, entities| {
+            entities.retain(|marker| *marker != entity);
+            !entities.is_empty()
+        });
+    }
+    for (entity, name) in added_q.iter() {
+        let key = HammerspaceConfig::strip_identifier(name.as_str(), &config.spawn_identifier);
+        markers.0.entry(key.to_string()).or_default().push(entity);
+    }
+}
+
+/// starts a level transition when the player walks into a portal, players arriving inside a
+/// portal have to leave it before it triggers
+pub(crate) fn enter_portals(
+    mut transition: ResMut<LevelTransition>,
+    mut transition_ev: EventWriter<LevelTransitionEvent>,
+    mut inside: Local<bool>,
+    player_q: Query<&GlobalTransform, With<Player>>,
+    portal_q: Query<(&Portal, &GlobalTransform, Option<&CollisionShape>)>,
+    settings: Res<TransitionSettings>,
+) {
+    let Ok(player) = player_q.get_single() else {
+        return;
+    };
+    let position = player.translation();
+    let portal = portal_q
+        .iter()
+        .find(|(portal, transform, shape)| overlaps(position, portal, transform, *shape));
+    let was_inside = std::mem::replace(&mut *inside, portal.is_some());
+    if was_inside || transition.is_active() {
+        return;
+    }
+    let Some((portal, ..)) = portal else {
+        return;
+    };
+
+    info!("entering portal to {} at {}", portal.level, portal.marker);
+    transition.0 = Some((
+        portal.level.clone(),
+        portal.marker.clone(),
+        TransitionPhase::FadeOut(Timer::from_seconds(settings.fade_duration, TimerMode::Once)),
+    ));
+    transition_ev.send(LevelTransitionEvent::FadeOut {
+        level: portal.level.clone(),
+        duration: settings.fade_duration,
+    });
+}
+
+fn overlaps(
+    position: Vec3,
+    portal: &Portal,
+    transform: &GlobalTransform,
+    shape: Option<&CollisionShape>,
+) -> bool {
+    match shape {
+        Some(shape) => shape.contains(transform, position),
+        None => transform.translation().distance(position) <= portal.radius,
+    }
+}
+
+/// walks the active transition through fading out, swapping the level, placing the player and
+/// fading back in
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn advance_transition(
+    mut transition: ResMut<LevelTransition>,
+    mut transition_ev: EventWriter<LevelTransitionEvent>,
+    mut swap_ev: EventWriter<SwapLevelEvent>,
+    mut spawn_ev: EventWriter<LocationSpawnEvent>,
+    mut result_ev: EventReader<LocationSpawnResultEvent>,
+    player_q: Query<Entity, With<Player>>,
+    parent_q: Query<&Parent>,
+    world_q: Query<(), With<GameWorldTag>>,
+    current: Option<Res<CurrentLevel>>,
+    load_state: Option<Res<State<AssetLoadState>>>,
+    settings: Res<TransitionSettings>,
+    time: Res<Time>,
+) {
+    let Some((level, marker, phase)) = transition.0.as_mut() else {
+        result_ev.clear();
+        return;
+    };
+    let state = load_state.map(|state| *state.get());
+    match phase {
+        TransitionPhase::FadeOut(timer) => {
+            if timer.tick(time.delta()).finished() {
+                // players spawned into the level go down with it and are spawned again at the marker
+                let player = player_q.get_single().ok().filter(|player| {
+                    !parent_q
+                        .iter_ancestors(*player)
+                        .any(|ancestor| world_q.contains(ancestor))
+                });
+                swap_ev.send(SwapLevelEvent(level.clone()));
+                *phase = TransitionPhase::Swapping(player);
+            }
+        }
+        TransitionPhase::Swapping(player) => {
+            // the marker is only looked up once the old level is gone
+            let swapped =
+                current.is_none_or(|current| current.0.as_deref() == Some(level.as_str()));
+            if swapped && state != Some(AssetLoadState::Loaded) {
+                spawn_ev.send(LocationSpawnEvent(Name::from(marker.clone()), *player));
+                *phase = TransitionPhase::Loading;
+            }
+        }
+        TransitionPhase::Loading => {
+            let placed = result_ev.read().any(|ev| ev.0.as_str() == marker.as_str());
+            if placed || state == Some(AssetLoadState::Failed) {
+                transition_ev.send(LevelTransitionEvent::FadeIn {
+                    level: level.clone(),
+                    duration: settings.fade_duration,
+                });
+                *phase = TransitionPhase::FadeIn(Timer::from_seconds(
+                    settings.fade_duration,
+                    TimerMode::Once,
+                ));
+            }
+        }
+        TransitionPhase::FadeIn(timer) => {
+            if timer.tick(time.delta()).finished() {
+                transition_ev.send(LevelTransitionEvent::Finished {
+                    level: level.clone(),
+                });
+                transition.0 = None;
+            }
+        }
+    }
+}
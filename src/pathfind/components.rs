@@ -1,14 +1,60 @@
-use std::sync::{Arc, RwLock};
-
-use bevy::prelude::*;
-use vleue_navigator::{NavMesh, Path};
+use bevy::{prelude::*, tasks::Task};
+use vleue_navigator::NavMesh;
 
+/// a path query running on the async compute pool, removing it cancels the query
 #[derive(Component)]
-pub struct FindingPath(pub Arc<RwLock<(Option<Path>, bool)>>);
+pub struct FindingPath(pub Task<Option<FoundPath>>);
 
-#[derive(Component)]
+/// result of a path query, becomes the `PathNodes` of the entity
+#[derive(Clone, Debug, Default)]
+pub struct FoundPath {
+    pub path: Vec<Vec2>,
+    pub links: Vec<LinkStep>,
+}
+
+/// moves the entity along its `PathNodes`
+#[derive(Component, Clone, Debug)]
 pub struct Navigator {
     pub speed: f32,
+    /// distance at which a waypoint counts as reached
+    pub arrival_radius: f32,
+    /// how quickly the entity turns to face where it is going, higher is snappier
+    pub turn_speed: f32,
+    /// picks the navmesh layer the entity paths on, see `PathfindSettings::agent_radii`
+    pub radius: f32,
+}
+
+impl Navigator {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            arrival_radius: 0.25,
+            turn_speed: 10.0,
+            radius: 0.5,
+        }
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+}
+
+/// Makes paths through the volume of the entity cost more, like water or mud.
+///
+/// Added to level objects from Blenvy custom properties, the volume comes from their
+/// `CollisionShape` or mesh bounds.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component, Default)]
+pub struct NavArea {
+    /// how many times more it costs to cross than normal ground
+    pub cost: f32,
+}
+
+impl Default for NavArea {
+    fn default() -> Self {
+        Self { cost: 2.0 }
+    }
 }
 
 #[derive(Component)]
@@ -20,4 +66,85 @@ pub struct Target {
 #[derive(Component)]
 pub struct PathNodes {
     pub path: Vec<Vec2>,
+    /// links on the way, in the order they are reached
+    pub links: Vec<LinkStep>,
+    /// link the entity is crossing, it leaves the ground until it reaches the far end
+    pub traversing: Option<LinkStep>,
 }
+
+impl From<FoundPath> for PathNodes {
+    fn from(found: FoundPath) -> Self {
+        Self {
+            path: found.path,
+            links: found.links,
+            traversing: None,
+        }
+    }
+}
+
+impl PathNodes {
+    /// drops the reached waypoint, returning the link that starts there if any
+    pub(crate) fn advance(&mut self) -> Option<LinkStep> {
+        if self.path.is_empty() {
+            return None;
+        }
+        self.path.remove(0);
+        let starts_here = self
+            .links
+            .first()
+            .is_some_and(|link| link.remaining == self.path.len());
+        starts_here.then(|| self.links.remove(0))
+    }
+}
+
+/// Connects the entity to the object named `to` for paths to cross, like a jump, a ladder or a
+/// teleporter.
+///
+/// Placed in Blender as a pair of empties with this custom property on the first one. Both ends
+/// should sit on the navmesh.
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct NavLink {
+    pub to: String,
+    pub kind: LinkKind,
+    /// added to the length of paths crossing the link
+    pub cost: f32,
+    /// whether the link can also be crossed from `to` back to the entity
+    pub bidirectional: bool,
+}
+
+impl Default for NavLink {
+    fn default() -> Self {
+        Self {
+            to: String::new(),
+            kind: LinkKind::default(),
+            cost: 1.0,
+            bidirectional: true,
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    #[default]
+    Jump,
+    Climb,
+    /// crossed instantly
+    Teleport,
+}
+
+/// a link crossed by a path
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkStep {
+    pub link: Entity,
+    pub kind: LinkKind,
+    pub from: Vec3,
+    pub to: Vec3,
+    /// waypoints left in the path once the start of the link is reached
+    pub remaining: usize,
+}
+
+/// child holding the derived shape of an `Obstacle` whose bounds are off its origin
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ObstacleFootprint(pub Entity);
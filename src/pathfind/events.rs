@@ -1,4 +1,31 @@
 use bevy::prelude::*;
 
+use super::components::LinkKind;
+
 #[derive(Event)]
 pub struct PathEvent(pub Entity, pub Vec3, pub Vec3);
+
+/// no path could be found for the last `PathEvent` of the entity
+#[derive(Event)]
+pub struct PathNotFound(pub Entity);
+
+/// the entity reached the end of its path
+#[derive(Event)]
+pub struct PathCompleted(pub Entity);
+
+/// the next waypoint of the entity left the navmesh, its path has been dropped
+#[derive(Event)]
+pub struct PathBlocked(pub Entity);
+
+/// The entity reached a `NavLink` and starts crossing it.
+///
+/// Followers move straight from `from` to `to` at their speed, teleports put them there at once.
+/// Gameplay code can play a jump or climb animation meanwhile.
+#[derive(Event, Clone, Debug)]
+pub struct TraverseLink {
+    pub entity: Entity,
+    pub link: Entity,
+    pub kind: LinkKind,
+    pub from: Vec3,
+    pub to: Vec3,
+}
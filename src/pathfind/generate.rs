@@ -0,0 +1,194 @@
+use bevy::prelude::*;
+
+use super::resources::{barycentric, NavGround, NavMeshGeneration};
+
+/// levels needing more cells than this are sampled on a coarser grid
+const MAX_CELLS: usize = 4_000_000;
+/// how far off a plane, in cells, corners of merged cells can be
+const PLANE_TOLERANCE: f32 = 1e-3;
+
+/// Samples the walkable ground of `triangles` (in world space) on a grid, merging cells on the
+/// same plane into larger polygons.
+///
+/// This is a 2.5D navmesh: only the highest surface over each cell counts, so floors under
+/// bridges or tables are lost. Cells next to a ledge higher than `step_height` are dropped so
+/// both sides of it stay apart in the navmesh.
+pub fn generate_ground(triangles: &[[Vec3; 3]], settings: &NavMeshGeneration) -> NavGround {
+    let Some((min, max)) = triangles
+        .iter()
+        .flatten()
+        .map(|point| (point.xz(), point.xz()))
+        .reduce(|(min, max), (point, _)| (min.min(point), max.max(point)))
+    else {
+        return NavGround::default();
+    };
+    let extent = max - min;
+    let mut cell = settings.cell_size.max(0.01);
+    let cells = (extent / cell).ceil();
+    if cells.x * cells.y > MAX_CELLS as f32 {
+        cell = (extent.x * extent.y / MAX_CELLS as f32).sqrt();
+        warn!(
+            "navmesh generation cell size raised to {} to fit the level",
+            cell
+        );
+    }
+    let width = (extent.x / cell).ceil().max(1.0) as usize;
+    let depth = (extent.y / cell).ceil().max(1.0) as usize;
+    let center = |x: usize, z: usize| min + (Vec2::new(x as f32, z as f32) + 0.5) * cell;
+
+    // highest surface over the center of each cell, and whether it can be walked on
+    let min_normal_y = settings.max_slope.cos();
+    let mut surface: Vec<Option<(f32, bool)>> = vec![None; width * depth];
+    for [a, b, c] in triangles {
+        let normal = (*b - *a).cross(*c - *a);
+        if normal.length_squared() < f32::EPSILON {
+            continue;
+        }
+        let walkable = normal.normalize().y >= min_normal_y;
+        let flat = [a.xz(), b.xz(), c.xz()];
+        let low = ((flat[0].min(flat[1]).min(flat[2]) - min) / cell - 0.5).ceil();
+        let high = ((flat[0].max(flat[1]).max(flat[2]) - min) / cell - 0.5).floor();
+        if high.x < 0.0 || high.y < 0.0 {
+            continue;
+        }
+        let low = low.max(Vec2::ZERO).as_uvec2();
+        let high = high
+            .as_uvec2()
+            .min(UVec2::new(width as u32 - 1, depth as u32 - 1));
+        for z in low.y as usize..=high.y as usize {
+            for x in low.x as usize..=high.x as usize {
+                let Some(weights) = barycentric(flat, center(x, z)) else {
+                    continue;
+                };
+                let height = weights.dot(Vec3::new(a.y, b.y, c.y));
+                let top = &mut surface[z * width + x];
+                if top.is_none_or(|(top, _)| height > top) {
+                    *top = Some((height, walkable));
+                }
+            }
+        }
+    }
+    let floor = |x: isize, z: isize| -> Option<f32> {
+        if x < 0 || z < 0 || x >= width as isize || z >= depth as isize {
+            return None;
+        }
+        match surface[z as usize * width + x as usize] {
+            Some((height, true)) => Some(height),
+            _ => None,
+        }
+    };
+
+    // keep cells away from walls, holes and ledges
+    let reach = (settings.agent_radius / cell).ceil() as isize;
+    let clearance = settings.agent_radius + cell * 0.5;
+    let kept: Vec<Option<f32>> = (0..width * depth)
+        .map(|index| {
+            let (x, z) = ((index % width) as isize, (index / width) as isize);
+            let height = floor(x, z)?;
+            let ledge = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|(dx, dz)| {
+                floor(x + dx, z + dz)
+                    .is_some_and(|other| (other - height).abs() > settings.step_height)
+            });
+            if ledge {
+                return None;
+            }
+            for dz in -reach..=reach {
+                for dx in -reach..=reach {
+                    let near = Vec2::new(dx as f32, dz as f32).length() * cell < clearance;
+                    if near && floor(x + dx, z + dz).is_none() {
+                        return None;
+                    }
+                }
+            }
+            Some(height)
+        })
+        .collect();
+
+    // corners take the average height of the kept cells around them
+    let corners = (width + 1) * (depth + 1);
+    let mut heights = vec![(0.0, 0); corners];
+    for (index, height) in kept.iter().enumerate() {
+        let Some(height) = height else {
+            continue;
+        };
+        let (x, z) = (index % width, index / width);
+        for corner in [(x, z), (x + 1, z), (x, z + 1), (x + 1, z + 1)] {
+            let (sum, count) = &mut heights[corner.1 * (width + 1) + corner.0];
+            *sum += height;
+            *count += 1;
+        }
+    }
+    let corner = |x: usize, z: usize| {
+        let (sum, count) = heights[z * (width + 1) + x];
+        let point = min + Vec2::new(x as f32, z as f32) * cell;
+        Vec3::new(point.x, sum / count.max(1) as f32, point.y)
+    };
+
+    // merge the kept cells into rectangles lying on a single plane
+    let flat = |x: usize, z: usize, (x0, z0): (usize, usize)| {
+        let base = corner(x0, z0).y;
+        let slope = Vec2::new(corner(x0 + 1, z0).y, corner(x0, z0 + 1).y) - base;
+        [(x, z), (x + 1, z), (x, z + 1), (x + 1, z + 1)]
+            .iter()
+            .all(|&(cx, cz)| {
+                let offset = Vec2::new(cx as f32 - x0 as f32, cz as f32 - z0 as f32);
+                (corner(cx, cz).y - base - slope.dot(offset)).abs() < PLANE_TOLERANCE * cell
+            })
+    };
+    let mut merged = vec![false; width * depth];
+    let mut rects = Vec::new();
+    for index in 0..width * depth {
+        if merged[index] || kept[index].is_none() {
+            continue;
+        }
+        let start = (index % width, index / width);
+        let free = |merged: &[bool], x: usize, z: usize| {
+            let index = z * width + x;
+            !merged[index] && kept[index].is_some() && flat(x, z, start)
+        };
+        let mut end_x = start.0 + 1;
+        while end_x < width && free(&merged, end_x, start.1) {
+            end_x += 1;
+        }
+        let mut end_z = start.1 + 1;
+        while end_z < depth && (start.0..end_x).all(|x| free(&merged, x, end_z)) {
+            end_z += 1;
+        }
+        for z in start.1..end_z {
+            merged[z * width + start.0..z * width + end_x].fill(true);
+        }
+        rects.push((start, (end_x, end_z)));
+    }
+
+    // rectangles keep the corners of their neighbours on their sides so they share whole edges
+    let mut used = vec![false; corners];
+    for &((x0, z0), (x1, z1)) in &rects {
+        for (x, z) in [(x0, z0), (x1, z0), (x0, z1), (x1, z1)] {
+            used[z * (width + 1) + x] = true;
+        }
+    }
+    let mut ground = NavGround::default();
+    for ((x0, z0), (x1, z1)) in rects {
+        // wound counter clockwise seen from above
+        let outline: Vec<(usize, usize)> = (z0..z1)
+            .map(|z| (x0, z))
+            .chain((x0..x1).map(|x| (x, z1)))
+            .chain((z0 + 1..=z1).rev().map(|z| (x1, z)))
+            .chain((x0 + 1..=x1).rev().map(|x| (x, z0)))
+            .filter(|(x, z)| used[z * (width + 1) + x])
+            .collect();
+        let (c00, c10) = (corner(x0, z0), corner(x1, z0));
+        let (c01, c11) = (corner(x0, z1), corner(x1, z1));
+        if outline.len() == 4 {
+            ground.0.push([c00, c01, c11]);
+            ground.0.push([c00, c11, c10]);
+            continue;
+        }
+        let center = (c00 + c11) / 2.0;
+        for (index, &(x, z)) in outline.iter().enumerate() {
+            let (nx, nz) = outline[(index + 1) % outline.len()];
+            ground.0.push([center, corner(x, z), corner(nx, nz)]);
+        }
+    }
+    ground
+}
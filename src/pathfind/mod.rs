@@ -1,22 +1,85 @@
 use bevy::prelude::*;
-use vleue_navigator::VleueNavigatorPlugin;
+use components::{LinkKind, NavArea, NavLink};
+use events::{PathBlocked, PathCompleted, PathNotFound, TraverseLink};
+use resources::{LevelNavMesh, PathfindSettings};
+use systems::{
+    build_navmesh, collect_nav_areas, collect_nav_links, derive_obstacle_shapes, follow_path,
+    get_path, poll_paths, spawn_navmesh_updaters, sync_obstacle_mode, sync_updated_navmesh,
+};
+use vleue_navigator::{prelude::PrimitiveObstacle, NavmeshUpdaterPlugin, VleueNavigatorPlugin};
 
 pub mod components;
 pub mod events;
+pub mod generate;
 pub mod resources;
 pub mod systems;
 
 pub struct PathFindPlugin;
 
-#[derive(Component)]
-pub struct Obstacle;
+/// Cuts a hole in the level navmesh around the entity.
+///
+/// The shape comes from a `PrimitiveObstacle` on the entity, or is derived from its
+/// `CollisionShape` or mesh bounds. Cached obstacles are for things that rarely move, like
+/// crates, live ones for doors and moving platforms.
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Obstacle {
+    #[default]
+    Live,
+    Cached,
+}
 
 impl Plugin for PathFindPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(VleueNavigatorPlugin);
-        // .add_systems(Update, get_path);
+        app.add_plugins((
+            VleueNavigatorPlugin,
+            NavmeshUpdaterPlugin::<PrimitiveObstacle, Obstacle>::default(),
+        ))
+        .init_resource::<LevelNavMesh>()
+        .init_resource::<PathfindSettings>()
+        .register_type::<NavArea>()
+        .register_type::<NavLink>()
+        .register_type::<LinkKind>()
+        .add_event::<PathNotFound>()
+        .add_event::<PathCompleted>()
+        .add_event::<PathBlocked>()
+        .add_event::<TraverseLink>()
+        .add_systems(
+            Update,
+            (
+                (derive_obstacle_shapes, sync_obstacle_mode),
+                (
+                    build_navmesh,
+                    collect_nav_areas,
+                    collect_nav_links,
+                    spawn_navmesh_updaters,
+                    sync_updated_navmesh,
+                    get_path,
+                    poll_paths,
+                    follow_path,
+                )
+                    .chain(),
+            ),
+        );
+        #[cfg(feature = "debug")]
+        app.add_systems(Startup, setup_obstacle_materials)
+            .add_systems(Update, systems::color_obstacles);
     }
 }
 
+/// id of the navmesh rebuilt around the obstacles of the current level, layers count up from it
+pub const LEVEL_NAVMESH_ID: u128 = 0x5a0f_3c1e_8d2b_4e6a_9f71_0b3d_c8e2_a4f6;
+
 pub const MATERIAL_OBSTACLE_LIVE: Handle<StandardMaterial> = Handle::weak_from_u128(0);
 pub const MATERIAL_OBSTACLE_CACHED: Handle<StandardMaterial> = Handle::weak_from_u128(1);
+
+#[cfg(feature = "debug")]
+fn setup_obstacle_materials(mut materials: ResMut<Assets<StandardMaterial>>) {
+    materials.insert(
+        &MATERIAL_OBSTACLE_LIVE,
+        StandardMaterial::from(Color::srgb(0.8, 0.2, 0.2)),
+    );
+    materials.insert(
+        &MATERIAL_OBSTACLE_CACHED,
+        StandardMaterial::from(Color::srgb(0.2, 0.2, 0.8)),
+    );
+}
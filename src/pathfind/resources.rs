@@ -1,5 +1,536 @@
-use bevy::prelude::*;
+use std::sync::Arc;
+
+use bevy::{
+    gltf::Gltf,
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+    },
+    tasks::Task,
+    utils::HashMap,
+};
 use vleue_navigator::NavMesh;
 
-// #[derive(Resource)]
-// pub struct Handles(pub Option<Handle<Gltf>>, pub Option<Handle<NavMesh>>);
+use super::components::LinkKind;
+
+#[derive(Resource, Clone, Debug)]
+pub struct PathfindSettings {
+    /// how many queued path requests are started each frame
+    pub queries_per_frame: usize,
+    /// seconds obstacles have to stay put before the navmesh is rebuilt around them
+    pub rebuild_debounce: f32,
+    /// agent radii a navmesh is built for, `Navigator`s use the smallest one they fit in
+    pub agent_radii: Vec<f32>,
+    /// builds navmeshes from the level geometry when it has no `HammerspaceConfig::navmesh_name`
+    pub generation: Option<NavMeshGeneration>,
+}
+
+impl Default for PathfindSettings {
+    fn default() -> Self {
+        Self {
+            queries_per_frame: 32,
+            rebuild_debounce: 0.2,
+            agent_radii: vec![0.5],
+            generation: None,
+        }
+    }
+}
+
+/// How navmeshes are generated for levels without a baked one, see `generate::generate_ground`.
+#[derive(Clone, Debug)]
+pub struct NavMeshGeneration {
+    pub source: GenerationSource,
+    /// steepest walkable slope, in radians
+    pub max_slope: f32,
+    /// highest ledge agents can walk up or down
+    pub step_height: f32,
+    /// distance kept from walls and ledges, on top of the radius of each layer
+    pub agent_radius: f32,
+    /// size of the grid the level is sampled on
+    pub cell_size: f32,
+}
+
+impl Default for NavMeshGeneration {
+    fn default() -> Self {
+        Self {
+            source: GenerationSource::default(),
+            max_slope: 45f32.to_radians(),
+            step_height: 0.3,
+            agent_radius: 0.0,
+            cell_size: 0.2,
+        }
+    }
+}
+
+/// level geometry navmeshes are generated from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GenerationSource {
+    /// nodes named with `HammerspaceConfig::collision_identifier`, and their children
+    #[default]
+    Colliders,
+    WholeLevel,
+}
+
+/// navmesh built from the current level, rebuilt whenever the level changes
+#[derive(Resource, Default)]
+pub struct LevelNavMesh {
+    pub level: Option<String>,
+    pub(crate) gltf: Option<Handle<Gltf>>,
+    /// ground being generated from the level when it has no navmesh of its own
+    pub(crate) generating: Option<Task<NavGround>>,
+    /// navmesh baked from the level, used by every layer until its own has been built
+    pub navmesh: Option<Handle<NavMesh>>,
+    /// navmeshes per agent radius, smallest radius first
+    pub layers: Vec<NavLayer>,
+    /// triangles of the navmesh in world space, used to put agents back on the ground
+    pub ground: NavGround,
+    /// `ground` bucketed on a grid, see `height_at`
+    pub(crate) ground_grid: GroundGrid,
+    pub costs: Arc<AreaCosts>,
+    /// `NavLink`s of the level, both ways for bidirectional ones
+    pub links: Arc<Vec<LinkEdge>>,
+}
+
+impl LevelNavMesh {
+    /// the smallest layer an agent of `radius` fits in, or the largest one
+    pub fn layer_for(&self, radius: f32) -> Option<&NavLayer> {
+        self.layers
+            .iter()
+            .find(|layer| layer.agent_radius >= radius)
+            .or(self.layers.last())
+    }
+
+    /// navmeshes of the layer an agent of `radius` fits in, or the baked navmesh until they
+    /// have all been built
+    pub fn navmeshes_for(&self, radius: f32) -> Vec<&Handle<NavMesh>> {
+        self.layer_for(radius)
+            .and_then(|layer| built(&layer.navmeshes))
+            .unwrap_or_else(|| self.navmesh.iter().collect())
+    }
+
+    /// detour navmeshes of the layer an agent of `radius` fits in, once they have all been built
+    pub fn detours_for(&self, radius: f32) -> Vec<&Handle<NavMesh>> {
+        self.layer_for(radius)
+            .and_then(|layer| built(&layer.detours))
+            .unwrap_or_default()
+    }
+
+    /// height of the ground under `point` (x, z), only looking at the triangles near it
+    pub fn height_at(&self, point: Vec2) -> Option<f32> {
+        self.ground_grid.height_at(&self.ground, point)
+    }
+
+    pub(crate) fn updaters(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.updaters.iter().chain(&layer.detour_updaters))
+            .copied()
+    }
+}
+
+/// one way across a `NavLink`, in world space
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkEdge {
+    pub link: Entity,
+    pub kind: LinkKind,
+    pub from: Vec3,
+    pub to: Vec3,
+    pub cost: f32,
+}
+
+/// Navmeshes shrunk by `agent_radius` and rebuilt around obstacles, one for each island of
+/// ground, see `NavGround::islands`.
+#[derive(Clone, Debug)]
+pub struct NavLayer {
+    pub agent_radius: f32,
+    /// `None` until the updater of the island has built it
+    pub navmeshes: Vec<Option<Handle<NavMesh>>>,
+    /// same navmeshes with the costly areas cut out, to look for a way around them
+    pub detours: Vec<Option<Handle<NavMesh>>>,
+    pub(crate) updaters: Vec<Entity>,
+    pub(crate) detour_updaters: Vec<Entity>,
+}
+
+impl NavLayer {
+    pub fn new(agent_radius: f32) -> Self {
+        Self {
+            agent_radius,
+            navmeshes: Vec::new(),
+            detours: Vec::new(),
+            updaters: Vec::new(),
+            detour_updaters: Vec::new(),
+        }
+    }
+}
+
+/// every navmesh, if there are some and they have all been built
+fn built(navmeshes: &[Option<Handle<NavMesh>>]) -> Option<Vec<&Handle<NavMesh>>> {
+    if navmeshes.is_empty() {
+        return None;
+    }
+    navmeshes.iter().map(Option::as_ref).collect()
+}
+
+/// How expensive it is to walk over parts of the level, 1.0 being normal ground.
+///
+/// Costly areas are avoided when going around them is cheaper, but never forbidden.
+#[derive(Default, Clone, Debug)]
+pub struct AreaCosts {
+    /// navmesh triangles with a `HammerspaceConfig::nav_cost_property` on them or their material
+    pub faces: Vec<([Vec3; 3], f32)>,
+    /// `NavArea` volumes on the xz plane
+    pub volumes: Vec<(Rect, f32)>,
+}
+
+impl AreaCosts {
+    /// distance between samples when pricing a path
+    const SAMPLE_STEP: f32 = 0.5;
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty() && self.volumes.is_empty()
+    }
+
+    /// whether some area is expensive enough to look for a way around it
+    pub fn has_avoided(&self) -> bool {
+        self.faces.iter().any(|(_, cost)| *cost > 1.0)
+            || self.volumes.iter().any(|(_, cost)| *cost > 1.0)
+    }
+
+    /// cost of the most expensive area at `point` (x, z)
+    pub fn cost_at(&self, point: Vec2) -> f32 {
+        let faces = self
+            .faces
+            .iter()
+            .filter(|([a, b, c], _)| barycentric([a.xz(), b.xz(), c.xz()], point).is_some())
+            .map(|(_, cost)| *cost);
+        let volumes = self
+            .volumes
+            .iter()
+            .filter(|(rect, _)| rect.contains(point))
+            .map(|(_, cost)| *cost);
+        faces.chain(volumes).reduce(f32::max).unwrap_or(1.0)
+    }
+
+    /// length of the path from `from` through `path`, weighted by the areas it crosses
+    pub fn path_cost(&self, from: Vec2, path: &[Vec2]) -> f32 {
+        let mut cost = 0.0;
+        let mut start = from;
+        for end in path {
+            let length = start.distance(*end);
+            let samples = (length / Self::SAMPLE_STEP).ceil().max(1.0);
+            for i in 0..samples as usize {
+                let t = (i as f32 + 0.5) / samples;
+                cost += length / samples * self.cost_at(start.lerp(*end, t));
+            }
+            start = *end;
+        }
+        cost
+    }
+
+    /// outlines of the areas to go around, cut out of the detour navmeshes
+    pub fn avoided_outlines(&self) -> Vec<Vec<Vec2>> {
+        let faces = NavGround(
+            self.faces
+                .iter()
+                .filter(|(_, cost)| *cost > 1.0)
+                .map(|(triangle, _)| *triangle)
+                .collect(),
+        );
+        let volumes = self
+            .volumes
+            .iter()
+            .filter(|(_, cost)| *cost > 1.0)
+            .map(|(rect, _)| {
+                vec![
+                    rect.min,
+                    Vec2::new(rect.max.x, rect.min.y),
+                    rect.max,
+                    Vec2::new(rect.min.x, rect.max.y),
+                ]
+            });
+        faces.boundary_loops().into_iter().chain(volumes).collect()
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct NavGround(pub Vec<[Vec3; 3]>);
+
+impl NavGround {
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return Self::default();
+        };
+        let positions: Vec<Vec3> = positions.iter().map(|p| Vec3::from_array(*p)).collect();
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+        Self(
+            indices
+                .chunks_exact(3)
+                .filter_map(|tri| {
+                    Some([
+                        *positions.get(tri[0])?,
+                        *positions.get(tri[1])?,
+                        *positions.get(tri[2])?,
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// Mesh of the ground with shared vertices welded together, for `NavMesh::from_bevy_mesh`.
+    pub fn to_mesh(&self) -> Mesh {
+        let key = |point: Vec3| (point * 1000.0).round().as_ivec3();
+        let mut welded: HashMap<IVec3, u32> = HashMap::default();
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        let mut indices = Vec::with_capacity(self.0.len() * 3);
+        for point in self.0.iter().flatten() {
+            let index = *welded.entry(key(*point)).or_insert_with(|| {
+                positions.push(point.to_array());
+                positions.len() as u32 - 1
+            });
+            indices.push(index);
+        }
+        let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_indices(Indices::U32(indices))
+    }
+
+    /// Outlines of the ground projected on the xz plane, the largest one first.
+    ///
+    /// Outlines wound like the triangles are outer edges of the ground, the others are holes in
+    /// it, see `islands`.
+    pub fn boundary_loops(&self) -> Vec<Vec<Vec2>> {
+        let key = |point: Vec2| (point * 1000.0).round().as_ivec2();
+        let point = |key: IVec2| key.as_vec2() / 1000.0;
+        let mut edges: HashMap<(IVec2, IVec2), (usize, IVec2, IVec2)> = HashMap::default();
+        for [a, b, c] in &self.0 {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                let (from, to) = (key(from.xz()), key(to.xz()));
+                let undirected = if from.to_array() <= to.to_array() {
+                    (from, to)
+                } else {
+                    (to, from)
+                };
+                edges.entry(undirected).or_insert((0, from, to)).0 += 1;
+            }
+        }
+
+        // an edge shared by two triangles is inside the mesh, where pieces of ground touch at a
+        // corner several boundary edges leave the same vertex
+        let mut outgoing: HashMap<IVec2, Vec<IVec2>> = HashMap::default();
+        for (_, from, to) in edges.into_values().filter(|(count, ..)| *count == 1) {
+            outgoing.entry(from).or_default().push(to);
+        }
+        let winding = self.winding();
+        let mut loops = Vec::new();
+        while let Some(start) = outgoing
+            .iter()
+            .find_map(|(from, to)| (!to.is_empty()).then_some(*from))
+        {
+            let first = outgoing.get_mut(&start).and_then(Vec::pop).unwrap();
+            let mut outline = vec![point(start)];
+            let (mut previous, mut current) = (start, first);
+            let closed = loop {
+                // keep turning towards the inside, so each outline goes around a single piece
+                // of ground instead of crossing over to the one touching it
+                let incoming = (current - previous).as_vec2();
+                let turn = |to: &IVec2| {
+                    let out = (*to - current).as_vec2();
+                    winding * incoming.perp_dot(out).atan2(incoming.dot(out))
+                };
+                let closing = (current == start).then_some(first);
+                let Some(next) = outgoing
+                    .get(&current)
+                    .into_iter()
+                    .flatten()
+                    .copied()
+                    .chain(closing)
+                    .max_by(|a, b| turn(a).total_cmp(&turn(b)))
+                else {
+                    break false;
+                };
+                if current == start && next == first {
+                    break true;
+                }
+                if let Some(to) = outgoing.get_mut(&current) {
+                    to.retain(|to| *to != next);
+                }
+                outline.push(point(current));
+                (previous, current) = (current, next);
+            };
+            if closed && outline.len() >= 3 {
+                loops.push(outline);
+            }
+        }
+        loops.sort_by(|a, b| signed_area(b).abs().total_cmp(&signed_area(a).abs()));
+        loops
+    }
+
+    /// The separate pieces of ground, each as its outer edge and the holes in it.
+    pub fn islands(&self) -> Vec<(Vec<Vec2>, Vec<Vec<Vec2>>)> {
+        let winding = self.winding();
+        let (outers, holes): (Vec<_>, Vec<_>) = self
+            .boundary_loops()
+            .into_iter()
+            .partition(|outline| signed_area(outline) * winding > 0.0);
+        let mut islands: Vec<(Vec<Vec2>, Vec<Vec<Vec2>>)> = outers
+            .into_iter()
+            .map(|outer| (outer, Vec::new()))
+            .collect();
+        for hole in holes {
+            // islands can sit in the holes of others, so the hole goes to the smallest one
+            // around it
+            let around = islands
+                .iter_mut()
+                .filter(|(outer, _)| hole.iter().any(|point| outline_contains(outer, *point)))
+                .min_by(|a, b| signed_area(&a.0).abs().total_cmp(&signed_area(&b.0).abs()));
+            if let Some((_, island_holes)) = around {
+                island_holes.push(hole);
+            }
+        }
+        islands
+    }
+
+    /// `1.0` when the triangles are wound counter clockwise on the xz plane, `-1.0` otherwise
+    fn winding(&self) -> f32 {
+        let area: f32 = self
+            .0
+            .iter()
+            .map(|[a, b, c]| (b.xz() - a.xz()).perp_dot(c.xz() - a.xz()))
+            .sum();
+        if area >= 0.0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    /// height of the ground under `point` (x, z), the highest surface where several overlap
+    pub fn height_at(&self, point: Vec2) -> Option<f32> {
+        self.0
+            .iter()
+            .filter_map(|[a, b, c]| {
+                let weights = barycentric([a.xz(), b.xz(), c.xz()], point)?;
+                Some(weights.dot(Vec3::new(a.y, b.y, c.y)))
+            })
+            .reduce(f32::max)
+    }
+}
+
+/// `NavGround` triangles bucketed by the cells of a grid over the xz plane they overlap
+#[derive(Default, Clone, Debug)]
+pub(crate) struct GroundGrid {
+    min: Vec2,
+    cell: f32,
+    width: usize,
+    cells: Vec<Vec<u32>>,
+}
+
+impl GroundGrid {
+    /// most cells along each side of the grid
+    const MAX_SIDE: f32 = 1024.0;
+
+    pub fn new(ground: &NavGround) -> Self {
+        let Some((min, max)) = ground
+            .0
+            .iter()
+            .flatten()
+            .map(|point| (point.xz(), point.xz()))
+            .reduce(|(min, max), (point, _)| (min.min(point), max.max(point)))
+        else {
+            return Self::default();
+        };
+        // about a couple of triangles per cell
+        let extent = (max - min).max(Vec2::splat(0.01));
+        let cell = (extent.x * extent.y * 2.0 / ground.0.len() as f32)
+            .sqrt()
+            .max(extent.max_element() / Self::MAX_SIDE);
+        let size = (extent / cell).ceil().as_uvec2().max(UVec2::ONE);
+        let mut grid = Self {
+            min,
+            cell,
+            width: size.x as usize,
+            cells: vec![Vec::new(); (size.x * size.y) as usize],
+        };
+        for (index, [a, b, c]) in ground.0.iter().enumerate() {
+            let low = grid.coord(a.xz().min(b.xz()).min(c.xz()));
+            let high = grid.coord(a.xz().max(b.xz()).max(c.xz()));
+            for z in low.y..=high.y {
+                for x in low.x..=high.x {
+                    grid.cells[z as usize * grid.width + x as usize].push(index as u32);
+                }
+            }
+        }
+        grid
+    }
+
+    /// cell of `point`, clamped to the grid
+    fn coord(&self, point: Vec2) -> UVec2 {
+        let depth = self.cells.len() / self.width.max(1);
+        ((point - self.min) / self.cell)
+            .max(Vec2::ZERO)
+            .as_uvec2()
+            .min(UVec2::new(self.width as u32, depth as u32).saturating_sub(UVec2::ONE))
+    }
+
+    /// height of `ground` under `point`, the highest surface where several overlap
+    pub fn height_at(&self, ground: &NavGround, point: Vec2) -> Option<f32> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        let coord = self.coord(point);
+        self.cells[coord.y as usize * self.width + coord.x as usize]
+            .iter()
+            .filter_map(|index| {
+                let [a, b, c] = ground.0.get(*index as usize)?;
+                let weights = barycentric([a.xz(), b.xz(), c.xz()], point)?;
+                Some(weights.dot(Vec3::new(a.y, b.y, c.y)))
+            })
+            .reduce(f32::max)
+    }
+}
+
+/// twice the area of `outline`, positive when it goes counter clockwise
+fn signed_area(outline: &[Vec2]) -> f32 {
+    outline
+        .iter()
+        .zip(outline.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum()
+}
+
+/// whether `point` is inside `outline`, by the number of edges a ray along x crosses
+fn outline_contains(outline: &[Vec2], point: Vec2) -> bool {
+    outline
+        .iter()
+        .zip(outline.iter().cycle().skip(1))
+        .filter(|(a, b)| {
+            (a.y > point.y) != (b.y > point.y)
+                && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// weights of the corners of `triangle` at `point`, if it is inside
+pub(crate) fn barycentric([a, b, c]: [Vec2; 3], point: Vec2) -> Option<Vec3> {
+    let area = (b - a).perp_dot(c - a);
+    if area.abs() < f32::EPSILON {
+        return None;
+    }
+    let u = (c - b).perp_dot(point - b) / area;
+    let v = (a - c).perp_dot(point - c) / area;
+    let w = 1.0 - u - v;
+    (u >= 0.0 && v >= 0.0 && w >= 0.0).then_some(Vec3::new(u, v, w))
+}
@@ -1,55 +1,859 @@
-use rand::Rng;
-use std::sync::{Arc, RwLock};
-
-use bevy::{gltf::GltfMesh, prelude::*, render::mesh::Mesh};
-use vleue_navigator::NavMesh;
-
-use super::{components::FindingPath, events::PathEvent};
-
-// pub fn get_path(
-//     mut commands: Commands,
-//     mut handles: ResMut<Handles>,
-//     gltfs: Res<Assets<Gltf>>,
-//     gltf_meshes: Res<Assets<GltfMesh>>,
-//     meshes: Res<Assets<Mesh>>,
-//     mut navmeshes: ResMut<Assets<NavMesh>>,
-//     mut path_ev: EventReader<PathEvent>,
-// ) {
-//     for ev in path_ev.read() {
-//         if handles.0.is_none() {
-//             return;
-//         }
-//         if handles.1.is_none() {
-//             // Get the gltf struct loaded from the file
-//             let Some(gltf) = gltfs.get(handles.0.as_mut().unwrap()) else {
-//                 return;
-//             };
-//             // Get the mesh called `navmesh`
-//             let Some(gltf_mesh) = gltf_meshes.get(&gltf.named_meshes["navmesh"]) else {
-//                 return;
-//             };
-//             // Get the actual mesh
-//             let Some(mesh) = meshes.get(&gltf_mesh.primitives[0].mesh) else {
-//                 return;
-//             };
-//             // Build a `NavMesh` from that mesh, then save it as an asset
-//             handles.1 = Some(navmeshes.add(NavMesh::from_bevy_mesh(mesh)));
-//         } else {
-//             // Get the navmesh, then search for a path
-//             let Some(navmesh) = navmeshes.get(handles.1.as_ref().unwrap()) else {
-//                 return;
-//             };
-
-//             let from = ev.1;
-//             let to = ev.2;
-//             let finding = FindingPath(Arc::new(RwLock::new((
-//                 navmesh.path(from.xz(), to.xz()),
-//                 false,
-//             ))));
-//             commands.entity(ev.0).insert(finding);
-//         }
-//     }
-// }
+use bevy::{
+    gltf::{Gltf, GltfMesh, GltfNode, GltfPrimitive},
+    prelude::*,
+    render::{mesh::Mesh, primitives::Aabb},
+    tasks::{block_on, poll_once, AsyncComputeTaskPool},
+    utils::HashSet,
+};
+use std::{f32::consts::FRAC_PI_2, sync::Arc};
+use vleue_navigator::{
+    prelude::{
+        CachableObstacle, ManagedNavMesh, NavMeshSettings, NavMeshStatus, NavMeshUpdateMode,
+        PrimitiveObstacle, Triangulation,
+    },
+    NavMesh,
+};
+
+use crate::{
+    assembler::{
+        components::{CollisionShape, LevelScoped},
+        resources::CurrentLevel,
+    },
+    resources::HammerspaceConfig,
+};
+
+use super::{
+    components::{
+        FindingPath, FoundPath, LinkKind, LinkStep, NavArea, NavLink, Navigator, ObstacleFootprint,
+        PathNodes,
+    },
+    events::{PathBlocked, PathCompleted, PathEvent, PathNotFound, TraverseLink},
+    generate::generate_ground,
+    resources::{
+        AreaCosts, GenerationSource, GroundGrid, LevelNavMesh, LinkEdge, NavGround, NavLayer,
+        PathfindSettings,
+    },
+    Obstacle, LEVEL_NAVMESH_ID,
+};
+
+/// Builds the navmesh from the mesh named `HammerspaceConfig::navmesh_name` in the current level.
+///
+/// Levels without one get a navmesh generated from their geometry when
+/// `PathfindSettings::generation` is set.
+#[allow(clippy::too_many_arguments)]
+pub fn build_navmesh(
+    mut commands: Commands,
+    mut level_navmesh: ResMut<LevelNavMesh>,
+    current: Res<CurrentLevel>,
+    config: Res<HammerspaceConfig>,
+    settings: Res<PathfindSettings>,
+    server: Res<AssetServer>,
+    gltfs: Res<Assets<Gltf>>,
+    gltf_nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    meshes: Res<Assets<Mesh>>,
+    mut navmeshes: ResMut<Assets<NavMesh>>,
+) {
+    if level_navmesh.level != current.0 {
+        for updater in level_navmesh.updaters() {
+            if let Some(mut updater) = commands.get_entity(updater) {
+                updater.despawn();
+            }
+        }
+        *level_navmesh = LevelNavMesh {
+            level: current.0.clone(),
+            gltf: current
+                .0
+                .as_ref()
+                .map(|level| server.load(config.level_path(level))),
+            ..default()
+        };
+    }
+    if level_navmesh.navmesh.is_some() {
+        return;
+    }
+    if let Some(task) = level_navmesh.generating.as_mut() {
+        let Some(ground) = block_on(poll_once(task)) else {
+            return;
+        };
+        level_navmesh.generating = None;
+        add_navmesh(&mut level_navmesh, &mut navmeshes, ground, Vec::new());
+        return;
+    }
+    // Get the gltf struct loaded from the file
+    let Some(gltf) = level_navmesh
+        .gltf
+        .as_ref()
+        .and_then(|handle| gltfs.get(handle))
+    else {
+        return;
+    };
+    let name = config.navmesh_name.as_str();
+    // Prefer the object so its transform is kept, falling back to the mesh data of that name
+    let node = gltf
+        .named_nodes
+        .get(name)
+        .and_then(|handle| gltf_nodes.get(handle))
+        .filter(|node| node.mesh.is_some());
+    let (gltf_mesh, transform) = match node {
+        Some(node) => (node.mesh.as_ref(), node.transform),
+        None => (gltf.named_meshes.get(name), Transform::IDENTITY),
+    };
+    let gltf_mesh = gltf_mesh.and_then(|handle| gltf_meshes.get(handle));
+    let mut ground = NavGround::default();
+    let mut faces = Vec::new();
+    match (gltf_mesh, &settings.generation) {
+        (Some(gltf_mesh), _) => {
+            // Gather the triangles of every primitive, each material can give its faces a cost
+            for primitive in &gltf_mesh.primitives {
+                let Some(mesh) = meshes.get(&primitive.mesh) else {
+                    return;
+                };
+                let triangles = NavGround::from_mesh(&mesh.clone().transformed_by(transform)).0;
+                let cost = primitive_cost(primitive, &config.nav_cost_property);
+                if cost != 1.0 {
+                    faces.extend(triangles.iter().map(|triangle| (*triangle, cost)));
+                }
+                ground.0.extend(triangles);
+            }
+        }
+        (None, Some(generation)) => {
+            let collider = match generation.source {
+                GenerationSource::Colliders => Some(config.collision_identifier.as_str()),
+                GenerationSource::WholeLevel => None,
+            };
+            let Some(triangles) =
+                level_triangles(gltf, &gltf_nodes, &gltf_meshes, &meshes, collider)
+            else {
+                return;
+            };
+            info!(
+                "generating navmesh for level {:?} from {} triangles",
+                level_navmesh.level,
+                triangles.len()
+            );
+            // large levels take a while to sample, keep it off the main thread
+            let generation = generation.clone();
+            let task = AsyncComputeTaskPool::get()
+                .spawn(async move { generate_ground(&triangles, &generation) });
+            level_navmesh.generating = Some(task);
+            return;
+        }
+        (None, None) => {
+            warn!("level {:?} has no mesh named {}", level_navmesh.level, name);
+            // don't look again until the level changes
+            level_navmesh.gltf = None;
+            return;
+        }
+    }
+    add_navmesh(&mut level_navmesh, &mut navmeshes, ground, faces);
+}
+
+/// Builds a `NavMesh` from the welded triangles of `ground`, then saves it as an asset
+fn add_navmesh(
+    level_navmesh: &mut LevelNavMesh,
+    navmeshes: &mut Assets<NavMesh>,
+    ground: NavGround,
+    faces: Vec<([Vec3; 3], f32)>,
+) {
+    let navmesh = if ground.0.is_empty() {
+        None
+    } else {
+        NavMesh::from_bevy_mesh(&ground.to_mesh())
+    };
+    let Some(navmesh) = navmesh else {
+        warn!("level {:?} has no walkable ground", level_navmesh.level);
+        level_navmesh.gltf = None;
+        return;
+    };
+    level_navmesh.navmesh = Some(navmeshes.add(navmesh));
+    level_navmesh.ground_grid = GroundGrid::new(&ground);
+    level_navmesh.ground = ground;
+    Arc::make_mut(&mut level_navmesh.costs).faces = faces;
+    info!("built navmesh for level {:?}", level_navmesh.level);
+}
+
+/// Triangles of the level in world space, only those under nodes named with `collider` if set.
+///
+/// `None` while some of the meshes are still loading.
+fn level_triangles(
+    gltf: &Gltf,
+    gltf_nodes: &Assets<GltfNode>,
+    gltf_meshes: &Assets<GltfMesh>,
+    meshes: &Assets<Mesh>,
+    collider: Option<&str>,
+) -> Option<Vec<[Vec3; 3]>> {
+    let children: HashSet<AssetId<GltfNode>> = gltf
+        .nodes
+        .iter()
+        .filter_map(|handle| gltf_nodes.get(handle))
+        .flat_map(|node| node.children.iter().map(Handle::id))
+        .collect();
+    let mut stack: Vec<(&Handle<GltfNode>, Transform, bool)> = gltf
+        .nodes
+        .iter()
+        .filter(|handle| !children.contains(&handle.id()))
+        .map(|handle| (handle, Transform::IDENTITY, collider.is_none()))
+        .collect();
+
+    let mut triangles = Vec::new();
+    while let Some((handle, parent, included)) = stack.pop() {
+        let node = gltf_nodes.get(handle)?;
+        let transform = parent * node.transform;
+        let included = included
+            || collider.is_some_and(|id| HammerspaceConfig::has_identifier(&node.name, id));
+        if let (true, Some(gltf_mesh)) = (included, &node.mesh) {
+            for primitive in &gltf_meshes.get(gltf_mesh)?.primitives {
+                let mesh = meshes.get(&primitive.mesh)?;
+                triangles.extend(NavGround::from_mesh(&mesh.clone().transformed_by(transform)).0);
+            }
+        }
+        stack.extend(
+            node.children
+                .iter()
+                .map(|child| (child, transform, included)),
+        );
+    }
+    Some(triangles)
+}
+
+/// `nav_cost` custom property of a navmesh primitive or its material, 1.0 when there is none
+fn primitive_cost(primitive: &GltfPrimitive, property: &str) -> f32 {
+    [&primitive.extras, &primitive.material_extras]
+        .into_iter()
+        .flatten()
+        .filter_map(|extras| serde_json::from_str::<serde_json::Value>(&extras.value).ok())
+        .find_map(|extras| match extras.get(property)? {
+            serde_json::Value::Number(cost) => cost.as_f64().map(|cost| cost as f32),
+            serde_json::Value::String(cost) => cost.parse().ok(),
+            _ => None,
+        })
+        .unwrap_or(1.0)
+}
+
+/// id of the navmesh managed by the updater of an island of a layer
+fn layer_navmesh_id(layer: usize, island: usize, detour: bool) -> u128 {
+    LEVEL_NAVMESH_ID + ((layer as u128) << 32) + island as u128 * 2 + detour as u128
+}
+
+/// Resolves the `NavLink`s of the level into the edges path queries can cross.
+///
+/// Links are resolved again when one changes or a link target moves or goes away, and, while a
+/// link leads to an object that has not spawned yet, whenever an object is named.
+#[allow(clippy::too_many_arguments)]
+pub fn collect_nav_links(
+    mut level_navmesh: ResMut<LevelNavMesh>,
+    link_q: Query<(Entity, &NavLink, &GlobalTransform)>,
+    named_q: Query<(Entity, &Name, &GlobalTransform)>,
+    changed_q: Query<
+        (),
+        (
+            With<NavLink>,
+            Or<(Changed<NavLink>, Changed<GlobalTransform>)>,
+        ),
+    >,
+    renamed_q: Query<(), Changed<Name>>,
+    moved_q: Query<(), Changed<GlobalTransform>>,
+    mut removed: RemovedComponents<NavLink>,
+    mut targets: Local<Vec<Entity>>,
+    mut unresolved: Local<bool>,
+) {
+    let removed = removed.read().count() > 0;
+    let targets_changed = targets
+        .iter()
+        .any(|target| moved_q.contains(*target) || !named_q.contains(*target));
+    let named = *unresolved && !renamed_q.is_empty();
+    if changed_q.is_empty() && !removed && !targets_changed && !named {
+        return;
+    }
+    targets.clear();
+    *unresolved = false;
+    let mut edges = Vec::new();
+    for (entity, link, transform) in link_q.iter() {
+        let Some((target, _, to)) = named_q.iter().find(|(_, name, _)| name.as_str() == link.to)
+        else {
+            // retried as objects get named, so only warn when the link itself changed
+            if changed_q.contains(entity) {
+                warn!("nav link {} leads to unknown object {}", entity, link.to);
+            }
+            *unresolved = true;
+            continue;
+        };
+        targets.push(target);
+        let (from, to) = (transform.translation(), to.translation());
+        edges.push(LinkEdge {
+            link: entity,
+            kind: link.kind,
+            from,
+            to,
+            cost: link.cost,
+        });
+        if link.bidirectional {
+            edges.push(LinkEdge {
+                link: entity,
+                kind: link.kind,
+                from: to,
+                to: from,
+                cost: link.cost,
+            });
+        }
+    }
+    if *level_navmesh.links != edges {
+        level_navmesh.links = Arc::new(edges);
+    }
+}
+
+/// Starts keeping navmeshes per agent radius up to date with obstacles once the level's has been
+/// built.
+///
+/// Each island of the baked navmesh gets its own navmesh per layer, its outline the fixed part
+/// of it, with `Obstacle`s cut out after `PathfindSettings::rebuild_debounce`. Layers get a
+/// second set of detour navmeshes when parts of the level cost more to cross.
+pub fn spawn_navmesh_updaters(
+    mut commands: Commands,
+    mut level_navmesh: ResMut<LevelNavMesh>,
+    settings: Res<PathfindSettings>,
+) {
+    if level_navmesh.navmesh.is_none() {
+        return;
+    }
+    let LevelNavMesh {
+        layers,
+        ground,
+        costs,
+        ..
+    } = &mut *level_navmesh;
+    if layers.is_empty() {
+        let mut radii = settings.agent_radii.clone();
+        radii.sort_by(f32::total_cmp);
+        radii.dedup();
+        *layers = radii.into_iter().map(NavLayer::new).collect();
+    }
+    let avoid = costs.has_avoided();
+    if !layers
+        .iter()
+        .any(|layer| layer.updaters.is_empty() || (avoid && layer.detour_updaters.is_empty()))
+    {
+        return;
+    }
+
+    let islands = ground.islands();
+    if islands.is_empty() {
+        return;
+    }
+    let avoided = if avoid {
+        costs.avoided_outlines()
+    } else {
+        Vec::new()
+    };
+    let mut spawn_updater = |agent_radius: f32, id: u128, island: usize, avoided: &[Vec<Vec2>]| {
+        let (outer, holes) = &islands[island];
+        let mut fixed = Triangulation::from_outer_edges(outer);
+        fixed.add_obstacles(holes.iter().chain(avoided).cloned());
+        commands
+            .spawn((
+                NavMeshSettings {
+                    fixed,
+                    agent_radius,
+                    ..default()
+                },
+                NavMeshUpdateMode::Debounced(settings.rebuild_debounce),
+                ManagedNavMesh::from_id(id),
+                // navmeshes are built on the xy plane, levels are laid out on xz
+                Transform::from_rotation(Quat::from_rotation_x(FRAC_PI_2)),
+                LevelScoped,
+            ))
+            .id()
+    };
+    for (index, layer) in layers.iter_mut().enumerate() {
+        if layer.updaters.is_empty() {
+            layer.updaters = (0..islands.len())
+                .map(|island| {
+                    let id = layer_navmesh_id(index, island, false);
+                    spawn_updater(layer.agent_radius, id, island, &[])
+                })
+                .collect();
+            layer.navmeshes = vec![None; islands.len()];
+        }
+        if avoid && layer.detour_updaters.is_empty() {
+            layer.detour_updaters = (0..islands.len())
+                .map(|island| {
+                    let id = layer_navmesh_id(index, island, true);
+                    spawn_updater(layer.agent_radius, id, island, &avoided)
+                })
+                .collect();
+            layer.detours = vec![None; islands.len()];
+        }
+    }
+}
+
+/// switches path queries over to the rebuilt navmeshes once their updater has finished them
+pub fn sync_updated_navmesh(
+    mut level_navmesh: ResMut<LevelNavMesh>,
+    status_q: Query<&NavMeshStatus, Changed<NavMeshStatus>>,
+) {
+    let built = |updater: &Entity| matches!(status_q.get(*updater), Ok(NavMeshStatus::Built));
+    for (index, layer) in level_navmesh.layers.iter_mut().enumerate() {
+        for (island, updater) in layer.updaters.iter().enumerate() {
+            if built(updater) {
+                let id = layer_navmesh_id(index, island, false);
+                layer.navmeshes[island] = Some(Handle::weak_from_u128(id));
+            }
+        }
+        for (island, updater) in layer.detour_updaters.iter().enumerate() {
+            if built(updater) {
+                let id = layer_navmesh_id(index, island, true);
+                layer.detours[island] = Some(Handle::weak_from_u128(id));
+            }
+        }
+    }
+}
+
+/// collects the `NavArea` volumes of the level, rebuilding the detour navmeshes when they change
+pub fn collect_nav_areas(
+    mut commands: Commands,
+    mut level_navmesh: ResMut<LevelNavMesh>,
+    area_q: Query<(
+        &NavArea,
+        &GlobalTransform,
+        Option<&CollisionShape>,
+        Option<&Aabb>,
+    )>,
+    changed_q: Query<
+        (),
+        (
+            With<NavArea>,
+            Or<(Changed<NavArea>, Changed<GlobalTransform>, Changed<Aabb>)>,
+        ),
+    >,
+    mut removed: RemovedComponents<NavArea>,
+) {
+    let removed = removed.read().count() > 0;
+    if changed_q.is_empty() && !removed {
+        return;
+    }
+    let volumes: Vec<(Rect, f32)> = area_q
+        .iter()
+        .filter_map(|(area, transform, shape, aabb)| {
+            let (min, max) = match (shape, aabb) {
+                (Some(shape), _) => shape.bounds(),
+                (None, Some(aabb)) => (aabb.min().into(), aabb.max().into()),
+                (None, None) => return None,
+            };
+            let mut corners = (0..8).map(|corner| {
+                let local = Vec3::select(
+                    BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                    max,
+                    min,
+                );
+                transform.transform_point(local).xz()
+            });
+            let first = corners.next()?;
+            let rect = corners.fold(Rect::from_corners(first, first), Rect::union_point);
+            Some((rect, area.cost))
+        })
+        .collect();
+    if volumes == level_navmesh.costs.volumes {
+        return;
+    }
+    Arc::make_mut(&mut level_navmesh.costs).volumes = volumes;
+    for layer in level_navmesh.layers.iter_mut() {
+        for updater in layer.detour_updaters.drain(..) {
+            if let Some(mut updater) = commands.get_entity(updater) {
+                updater.despawn();
+            }
+        }
+        layer.detours.clear();
+    }
+}
+
+/// Gives obstacles without a primitive shape one from their collision shape or mesh bounds.
+///
+/// Bounds that are not centered on the origin of the entity get their shape on an
+/// `ObstacleFootprint` child placed at their center.
+pub fn derive_obstacle_shapes(
+    mut commands: Commands,
+    obstacle_q: Query<
+        (Entity, &Obstacle, Option<&CollisionShape>, Option<&Aabb>),
+        (Without<PrimitiveObstacle>, Without<ObstacleFootprint>),
+    >,
+) {
+    for (entity, obstacle, shape, aabb) in obstacle_q.iter() {
+        let (min, max) = match (shape, aabb) {
+            (Some(shape), _) => shape.bounds(),
+            (None, Some(aabb)) => (aabb.min().into(), aabb.max().into()),
+            // mesh bounds are computed after spawning, try again next frame
+            (None, None) => continue,
+        };
+        let size = (max - min).xz();
+        let center = (min + max) / 2.0;
+        let rectangle = PrimitiveObstacle::Rectangle(Rectangle::new(size.x, size.y));
+        if center.xz().length_squared() < f32::EPSILON {
+            commands.entity(entity).insert(rectangle);
+            continue;
+        }
+        let footprint = commands
+            .spawn((
+                *obstacle,
+                rectangle,
+                Transform::from_translation(center.with_y(0.0)),
+            ))
+            .set_parent(entity)
+            .id();
+        commands.entity(entity).insert(ObstacleFootprint(footprint));
+    }
+}
+
+/// cached obstacles are only triangulated once, live ones every time the navmesh is rebuilt
+pub fn sync_obstacle_mode(
+    mut commands: Commands,
+    obstacle_q: Query<(Entity, &Obstacle, Option<&ObstacleFootprint>), Changed<Obstacle>>,
+) {
+    for (entity, obstacle, footprint) in obstacle_q.iter() {
+        if let Some(footprint) = footprint {
+            commands.entity(footprint.0).insert(*obstacle);
+        }
+        match obstacle {
+            Obstacle::Cached => {
+                commands.entity(entity).insert(CachableObstacle);
+            }
+            Obstacle::Live => {
+                commands.entity(entity).remove::<CachableObstacle>();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "debug")]
+pub fn color_obstacles(
+    mut commands: Commands,
+    obstacle_q: Query<(Entity, &Obstacle), (Changed<Obstacle>, With<Mesh3d>)>,
+) {
+    for (entity, obstacle) in obstacle_q.iter() {
+        let material = match obstacle {
+            Obstacle::Live => super::MATERIAL_OBSTACLE_LIVE,
+            Obstacle::Cached => super::MATERIAL_OBSTACLE_CACHED,
+        };
+        commands.entity(entity).insert(MeshMaterial3d(material));
+    }
+}
+
+/// Queues path requests and starts them on the async compute pool, a few per frame.
+///
+/// A newer request for an entity replaces its queued one and cancels the query in flight.
+/// Navigators path on the layer matching their radius, taking the detour around costly areas
+/// when it is cheaper.
+#[allow(clippy::too_many_arguments)]
+pub fn get_path(
+    mut commands: Commands,
+    mut path_ev: EventReader<PathEvent>,
+    mut pending: Local<Vec<(Entity, Vec3, Vec3)>>,
+    finding_q: Query<(), With<FindingPath>>,
+    navigator_q: Query<&Navigator>,
+    level_navmesh: Res<LevelNavMesh>,
+    navmeshes: Res<Assets<NavMesh>>,
+    settings: Res<PathfindSettings>,
+) {
+    for ev in path_ev.read() {
+        pending.retain(|(entity, ..)| *entity != ev.0);
+        if finding_q.contains(ev.0) {
+            commands.entity(ev.0).remove::<FindingPath>();
+        }
+        pending.push((ev.0, ev.1, ev.2));
+    }
+    // Wait for the baked navmesh, every layer falls back to it
+    let Some(baked) = level_navmesh
+        .navmesh
+        .as_ref()
+        .and_then(|handle| navmeshes.get(handle))
+    else {
+        return;
+    };
+
+    let pool = AsyncComputeTaskPool::get();
+    let count = settings.queries_per_frame.min(pending.len());
+    for (entity, from, to) in pending.drain(..count) {
+        let Some(mut entity_commands) = commands.get_entity(entity) else {
+            continue;
+        };
+        let radius = navigator_q
+            .get(entity)
+            .map_or(0.0, |navigator| navigator.radius);
+        let loaded = |handles: Vec<&Handle<NavMesh>>| -> Vec<NavMesh> {
+            handles
+                .into_iter()
+                .filter_map(|handle| navmeshes.get(handle))
+                .cloned()
+                .collect()
+        };
+        let mut islands = loaded(level_navmesh.navmeshes_for(radius));
+        if islands.is_empty() {
+            islands.push(baked.clone());
+        }
+        let query = PathQuery {
+            navmeshes: islands,
+            detours: loaded(level_navmesh.detours_for(radius)),
+            costs: level_navmesh.costs.clone(),
+            links: level_navmesh.links.clone(),
+        };
+        let task = pool.spawn(async move { query.find(from, to) });
+        entity_commands.insert(FindingPath(task));
+    }
+}
+
+/// a path request as it runs on the async compute pool
+struct PathQuery {
+    /// one navmesh per island of ground
+    navmeshes: Vec<NavMesh>,
+    detours: Vec<NavMesh>,
+    costs: Arc<AreaCosts>,
+    links: Arc<Vec<LinkEdge>>,
+}
+
+#[derive(Clone)]
+enum PathStep {
+    Walk(Vec<Vec2>),
+    Link(usize),
+}
+
+impl PathQuery {
+    /// cheapest walk between two points, taking the detour around costly areas when it pays off
+    fn walk(&self, from: Vec2, to: Vec2) -> Option<(Vec<Vec2>, f32)> {
+        // walks stay on one island, links lead from one to another
+        let priced = |navmeshes: &[NavMesh]| {
+            let path = navmeshes
+                .iter()
+                .find_map(|navmesh| navmesh.path(from, to))?
+                .path;
+            let cost = self.costs.path_cost(from, &path);
+            Some((path, cost))
+        };
+        match (priced(&self.navmeshes), priced(&self.detours)) {
+            (Some(direct), Some(detour)) if detour.1 < direct.1 => Some(detour),
+            (direct, detour) => direct.or(detour),
+        }
+    }
+
+    /// Cheapest way from `from` to `to`, walking the navmesh and crossing links.
+    ///
+    /// Dijkstra over both ends of the path and of every link, walks between them are only looked
+    /// up when they are needed.
+    fn find(&self, from: Vec3, to: Vec3) -> Option<FoundPath> {
+        if self.links.is_empty() {
+            let (path, _) = self.walk(from.xz(), to.xz())?;
+            return Some(FoundPath {
+                path,
+                links: Vec::new(),
+            });
+        }
+        // 0 is the start, 1 the destination, then the start and end of each link
+        let mut points = vec![from.xz(), to.xz()];
+        points.extend(
+            self.links
+                .iter()
+                .flat_map(|edge| [edge.from.xz(), edge.to.xz()]),
+        );
+        let count = points.len();
+        let link_start = |node: usize| node >= 2 && node % 2 == 0;
+
+        let mut best = vec![f32::INFINITY; count];
+        let mut done = vec![false; count];
+        let mut previous: Vec<Option<(usize, PathStep)>> = vec![None; count];
+        best[0] = 0.0;
+        while let Some(node) = (0..count)
+            .filter(|node| !done[*node] && best[*node].is_finite())
+            .min_by(|a, b| best[*a].total_cmp(&best[*b]))
+        {
+            if node == 1 {
+                break;
+            }
+            done[node] = true;
+            if link_start(node) {
+                let next = node + 1;
+                let cost = best[node] + self.links[(node - 2) / 2].cost;
+                if cost < best[next] {
+                    best[next] = cost;
+                    previous[next] = Some((node, PathStep::Link((node - 2) / 2)));
+                }
+                continue;
+            }
+            // walks go from the start or the end of a link to the destination or a link start
+            for next in (1..count).filter(|next| *next == 1 || link_start(*next)) {
+                if done[next] {
+                    continue;
+                }
+                let Some((path, cost)) = self.walk(points[node], points[next]) else {
+                    continue;
+                };
+                if best[node] + cost < best[next] {
+                    best[next] = best[node] + cost;
+                    previous[next] = Some((node, PathStep::Walk(path)));
+                }
+            }
+        }
+        if !best[1].is_finite() {
+            return None;
+        }
+
+        let mut steps = Vec::new();
+        let mut node = 1;
+        while let Some((from, step)) = previous[node].take() {
+            steps.push(step);
+            node = from;
+        }
+        let mut found = FoundPath::default();
+        for step in steps.into_iter().rev() {
+            match step {
+                PathStep::Walk(path) => found.path.extend(path),
+                PathStep::Link(index) => {
+                    let edge = &self.links[index];
+                    // the link starts on a waypoint so followers know when they reach it
+                    if found.path.last() != Some(&edge.from.xz()) {
+                        found.path.push(edge.from.xz());
+                    }
+                    found.links.push(LinkStep {
+                        link: edge.link,
+                        kind: edge.kind,
+                        from: edge.from,
+                        to: edge.to,
+                        remaining: found.path.len(),
+                    });
+                    found.path.push(edge.to.xz());
+                }
+            }
+        }
+        for link in found.links.iter_mut() {
+            link.remaining = found.path.len() - link.remaining;
+        }
+        Some(found)
+    }
+}
+
+/// hands finished path queries to their entities as `PathNodes`
+pub fn poll_paths(
+    mut commands: Commands,
+    mut finding_q: Query<(Entity, &mut FindingPath)>,
+    mut not_found_ev: EventWriter<PathNotFound>,
+) {
+    for (entity, mut finding) in finding_q.iter_mut() {
+        let Some(result) = block_on(poll_once(&mut finding.0)) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.remove::<FindingPath>();
+        match result {
+            Some(found) => {
+                entity_commands.insert(PathNodes::from(found));
+            }
+            None => {
+                debug!("no path for {}", entity);
+                entity_commands.remove::<PathNodes>();
+                not_found_ev.send(PathNotFound(entity));
+            }
+        }
+    }
+}
+
+/// Steers navigators through their waypoints, keeping them on the ground of the navmesh.
+///
+/// Links are crossed in a straight line, off the ground, after sending a `TraverseLink`.
+#[allow(clippy::too_many_arguments)]
+pub fn follow_path(
+    mut commands: Commands,
+    mut nav_q: Query<(Entity, &Navigator, &mut PathNodes, &mut Transform)>,
+    mut completed_ev: EventWriter<PathCompleted>,
+    mut blocked_ev: EventWriter<PathBlocked>,
+    mut traverse_ev: EventWriter<TraverseLink>,
+    level_navmesh: Res<LevelNavMesh>,
+    navmeshes: Res<Assets<NavMesh>>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_secs();
+    let mut start_link = |entity: Entity, nodes: &mut PathNodes, link: Option<LinkStep>| {
+        if let Some(link) = &link {
+            traverse_ev.send(TraverseLink {
+                entity,
+                link: link.link,
+                kind: link.kind,
+                from: link.from,
+                to: link.to,
+            });
+        }
+        nodes.traversing = link;
+    };
+
+    for (entity, navigator, mut nodes, mut transform) in nav_q.iter_mut() {
+        let islands: Vec<&NavMesh> = level_navmesh
+            .navmeshes_for(navigator.radius)
+            .into_iter()
+            .filter_map(|handle| navmeshes.get(handle))
+            .collect();
+        let position = transform.translation;
+        let mut step = navigator.speed * dt;
+
+        if let Some(link) = nodes.traversing.clone() {
+            let to_end = link.to - transform.translation;
+            let distance = to_end.length();
+            if link.kind == LinkKind::Teleport || distance <= step {
+                transform.translation = link.to;
+                if link.kind != LinkKind::Teleport {
+                    step -= distance;
+                }
+                // the far end is a waypoint too, another link may start there
+                let next_link = nodes.advance();
+                start_link(entity, &mut nodes, next_link);
+            } else {
+                transform.translation += to_end / distance * step;
+                step = 0.0;
+            }
+        }
+
+        while step > 0.0 && nodes.traversing.is_none() {
+            let Some(next) = nodes.path.first().copied() else {
+                break;
+            };
+            if !islands.is_empty() && !islands.iter().any(|navmesh| navmesh.is_in_mesh(next)) {
+                blocked_ev.send(PathBlocked(entity));
+                commands.entity(entity).remove::<PathNodes>();
+                break;
+            }
+            let to_next = next - transform.translation.xz();
+            let distance = to_next.length();
+            if distance <= step {
+                // reached, carry on to the next waypoint with what is left of the step
+                transform.translation.x = next.x;
+                transform.translation.z = next.y;
+                step -= distance;
+            } else if distance > navigator.arrival_radius {
+                let moved = to_next / distance * step;
+                transform.translation.x += moved.x;
+                transform.translation.z += moved.y;
+                break;
+            }
+            let next_link = nodes.advance();
+            start_link(entity, &mut nodes, next_link);
+        }
+
+        let travelled = transform.translation.xz() - position.xz();
+        if nodes.traversing.is_none() {
+            if let Some(height) = level_navmesh.height_at(transform.translation.xz()) {
+                transform.translation.y = height;
+            }
+        }
+        if travelled.length_squared() > f32::EPSILON {
+            let facing = Transform::IDENTITY
+                .looking_to(Vec3::new(travelled.x, 0.0, travelled.y), Vec3::Y)
+                .rotation;
+            let turn = (navigator.turn_speed * dt).min(1.0);
+            transform.rotation = transform.rotation.slerp(facing, turn);
+        }
+
+        if nodes.path.is_empty() && nodes.traversing.is_none() {
+            completed_ev.send(PathCompleted(entity));
+            commands.entity(entity).remove::<PathNodes>();
+        }
+    }
+}
 
 // pub fn pick_point_in_range(center: Vec3, range: (Vec3, Vec3)) -> Vec3 {
 //     let range_x = rand::thread_rng().gen_range(center.x - range.0.x..center.x + range.1.x);
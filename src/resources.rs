@@ -1,24 +1,87 @@
+use std::fmt;
+
 use bevy::prelude::*;
 
-#[derive(Resource,Clone)]
+#[derive(Resource, Clone)]
 pub struct HammerspaceConfig {
     pub level_folder: String,
+    pub blueprint_folder: String,
+    pub image_folder: String,
+    pub sprite_sheet_folder: String,
+    /// extension of the blueprints and levels exported from blender
+    pub blueprint_extension: String,
+    /// extension of the scenes and meshes listed in `SessionAssets`
+    pub mesh_extension: String,
     pub lights_identifier: String,
     pub collision_identifier: String,
     pub spawn_identifier: String,
+    /// blueprint spawned for a `LocationSpawnEvent` that does not name an entity to move
+    pub player_blueprint: String,
+    /// name of the level object (or mesh) the navmesh is built from
+    pub navmesh_name: String,
+    /// custom property on navmesh faces (or their material) making them cost more to cross
+    pub nav_cost_property: String,
+    /// light inserted on level nodes ending in `lights_identifier`
+    pub level_light: PointLight,
+    /// derive collision shapes from the convex hull of the collider mesh instead of its bounds
+    pub convex_colliders: bool,
 }
 
 impl Default for HammerspaceConfig {
     fn default() -> Self {
         Self {
-            level_folder: "".to_string(),
+            level_folder: "levels".to_string(),
+            blueprint_folder: "blueprints".to_string(),
+            image_folder: "images".to_string(),
+            sprite_sheet_folder: "images/sprites".to_string(),
+            blueprint_extension: "glb".to_string(),
+            mesh_extension: "gltf".to_string(),
             lights_identifier: "_light".to_string(),
-            collision_identifier: "_colllider".to_string(),
+            collision_identifier: "_collider".to_string(),
             spawn_identifier: "_spawn".to_string(),
+            player_blueprint: "player".to_string(),
+            navmesh_name: "navmesh".to_string(),
+            nav_cost_property: "nav_cost".to_string(),
+            level_light: PointLight {
+                shadows_enabled: true,
+                ..default()
+            },
+            convex_colliders: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    InvalidFolder {
+        field: &'static str,
+        path: String,
+    },
+    InvalidExtension {
+        field: &'static str,
+        extension: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidFolder { field, path } => write!(
+                f,
+                "{} \"{}\" must be a relative asset folder without \"..\" or backslashes",
+                field, path
+            ),
+            ConfigError::InvalidExtension { field, extension } => write!(
+                f,
+                "{} \"{}\" must be one of \"glb\" or \"gltf\"",
+                field, extension
+            ),
         }
     }
 }
 
+impl std::error::Error for ConfigError {}
+
 impl HammerspaceConfig {
     pub fn new(level_folder: String) -> Self {
         Self {
@@ -26,4 +89,92 @@ impl HammerspaceConfig {
             ..default()
         }
     }
+
+    /// checks that every folder is a relative asset path and every extension is a gltf format
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (field, path) in [
+            ("level_folder", &self.level_folder),
+            ("blueprint_folder", &self.blueprint_folder),
+            ("image_folder", &self.image_folder),
+            ("sprite_sheet_folder", &self.sprite_sheet_folder),
+        ] {
+            if path.starts_with('/')
+                || path.contains('\\')
+                || path.contains("://")
+                || path.split('/').any(|part| part == "..")
+            {
+                return Err(ConfigError::InvalidFolder {
+                    field,
+                    path: path.clone(),
+                });
+            }
+        }
+        for (field, extension) in [
+            ("blueprint_extension", &self.blueprint_extension),
+            ("mesh_extension", &self.mesh_extension),
+        ] {
+            if !matches!(extension.as_str(), "glb" | "gltf") {
+                return Err(ConfigError::InvalidExtension {
+                    field,
+                    extension: extension.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// joins a configured folder and a file, an empty folder is the asset root
+    pub fn join(folder: &str, file: &str) -> String {
+        let folder = folder.trim_end_matches('/');
+        if folder.is_empty() {
+            file.to_string()
+        } else {
+            format!("{}/{}", folder, file)
+        }
+    }
+
+    /// whether a level node name carries the given identifier, ignoring the `.001` style
+    /// suffix blender adds to duplicated objects
+    pub fn has_identifier(name: &str, identifier: &str) -> bool {
+        !identifier.is_empty() && Self::strip_duplicate_suffix(name).ends_with(identifier)
+    }
+
+    /// the node name without the identifier or blender duplicate suffix, `door_spawn.001` becomes `door`
+    pub fn strip_identifier<'a>(name: &'a str, identifier: &str) -> &'a str {
+        let name = Self::strip_duplicate_suffix(name);
+        name.strip_suffix(identifier).unwrap_or(name)
+    }
+
+    fn strip_duplicate_suffix(name: &str) -> &str {
+        match name.rsplit_once('.') {
+            Some((base, suffix))
+                if !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                base
+            }
+            _ => name,
+        }
+    }
+
+    pub fn level_path(&self, name: &str) -> String {
+        Self::join(
+            &self.level_folder,
+            &format!("{}.{}", name, self.blueprint_extension),
+        )
+    }
+
+    /// manifests shipped next to a level, in the order they are looked for
+    pub fn level_manifest_paths(&self, name: &str) -> Vec<String> {
+        ["manifest.ron", "manifest.json"]
+            .iter()
+            .map(|extension| Self::join(&self.level_folder, &format!("{}.{}", name, extension)))
+            .collect()
+    }
+
+    pub fn blueprint_path(&self, name: &str) -> String {
+        Self::join(
+            &self.blueprint_folder,
+            &format!("{}.{}", name, self.blueprint_extension),
+        )
+    }
 }
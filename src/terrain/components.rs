@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+
+use super::{heightmap::Heightmap, MATERIAL_TERRAIN};
+
+/// Builds terrain chunks from `heightmap` as children of the entity, each tagged `LevelTerrain`.
+///
+/// Chunks switch to a coarser mesh at every distance in `lod_distances` from the closest
+/// active camera.
+#[derive(Component, Clone, Debug)]
+#[require(Transform, Visibility)]
+pub struct Terrain {
+    pub heightmap: Handle<Heightmap>,
+    pub material: Handle<StandardMaterial>,
+    /// quads along each side of a chunk at full detail
+    pub chunk_size: u32,
+    pub lod_distances: Vec<f32>,
+    /// how far the chunk edges reach down to hide cracks between LODs
+    pub skirt: f32,
+}
+
+impl Terrain {
+    pub fn new(heightmap: Handle<Heightmap>) -> Self {
+        Self {
+            heightmap,
+            material: MATERIAL_TERRAIN,
+            chunk_size: 64,
+            lod_distances: vec![64.0, 128.0, 256.0],
+            skirt: 1.0,
+        }
+    }
+
+    pub fn with_material(mut self, material: Handle<StandardMaterial>) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_lod_distances(mut self, lod_distances: Vec<f32>) -> Self {
+        self.lod_distances = lod_distances;
+        self
+    }
+
+    /// LOD for a chunk `distance` away from the camera, `0` being full detail
+    pub fn lod_at(&self, distance: f32) -> u32 {
+        self.lod_distances
+            .iter()
+            .filter(|lod_distance| distance > **lod_distance)
+            .count() as u32
+    }
+}
+
+/// one chunk of a `Terrain`, the meshes of its LODs are built when first needed
+#[derive(Component, Debug)]
+pub struct TerrainChunk {
+    pub coord: UVec2,
+    pub lod: Option<u32>,
+    /// center of the chunk relative to the terrain entity
+    pub(crate) center: Vec3,
+    pub(crate) meshes: Vec<Option<Handle<Mesh>>>,
+}
+
+/// Loads the heightmap file, or `.terrain.ron` noise, at `path` into a `Terrain` on the entity,
+/// `size` and `height` place it in the world. `material` is the asset path of a
+/// `StandardMaterial`, like `levels/hub.glb#Material0`, the plain terrain material is used
+/// without one.
+///
+/// Can be set on level nodes from a Blenvy custom property.
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component, Default)]
+#[require(Transform, Visibility)]
+pub struct TerrainHeightmap {
+    pub path: String,
+    pub size: Vec2,
+    pub height: f32,
+    pub material: String,
+}
+
+impl Default for TerrainHeightmap {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            size: Vec2::splat(256.0),
+            height: 64.0,
+            material: String::new(),
+        }
+    }
+}
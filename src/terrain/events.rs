@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use super::export::ExportFormat;
+
+/// Writes the terrain of `entity` to `path`.
+///
+/// A `Terrain` is written from its heightmap at full detail, otherwise the meshes of every
+/// `LevelTerrain` in the hierarchy of `entity` are merged, relative to `entity`.
+#[derive(Event, Clone, Debug)]
+pub struct ExportTerrain {
+    pub entity: Entity,
+    pub path: PathBuf,
+    pub format: ExportFormat,
+}
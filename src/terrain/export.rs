@@ -0,0 +1,265 @@
+use std::{fmt, io::Write};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{PrimitiveTopology, VertexAttributeValues},
+};
+use serde_json::json;
+use stl_io::{Normal, Triangle, Vertex};
+
+use super::{heightmap::Heightmap, mesh::chunk_mesh};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    StlBinary,
+    StlAscii,
+    /// binary glTF, as read from `levels/<name>.glb`
+    Glb,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(std::io::Error),
+    /// the mesh is not a triangle list with positions
+    UnsupportedMesh,
+    /// nothing to export on the entity
+    NoMesh,
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(err) => write!(f, "could not write terrain: {}", err),
+            ExportError::UnsupportedMesh => {
+                write!(f, "only triangle lists with positions can be exported")
+            }
+            ExportError::NoMesh => write!(f, "no terrain mesh to export"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<std::io::Error> for ExportError {
+    fn from(err: std::io::Error) -> Self {
+        ExportError::Io(err)
+    }
+}
+
+/// the whole of `heightmap` as one mesh at full detail
+pub fn heightmap_mesh(heightmap: &Heightmap) -> Mesh {
+    let chunk_size = heightmap.width.max(heightmap.depth);
+    chunk_mesh(heightmap, UVec2::ZERO, chunk_size, 0, 0.0)
+}
+
+/// writes `mesh` to `writer` in `format`, `name` is used where the format has one
+pub fn export_mesh(
+    mesh: &Mesh,
+    name: &str,
+    format: ExportFormat,
+    writer: &mut impl Write,
+) -> Result<(), ExportError> {
+    match format {
+        ExportFormat::StlBinary => write_stl(mesh, writer),
+        ExportFormat::StlAscii => write_ascii_stl(mesh, name, writer),
+        ExportFormat::Glb => write_glb(mesh, name, writer),
+    }
+}
+
+/// positions and triangle indices of a triangle list mesh
+fn triangles(mesh: &Mesh) -> Result<(&[[f32; 3]], Vec<u32>), ExportError> {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Err(ExportError::UnsupportedMesh);
+    }
+    let positions = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(VertexAttributeValues::as_float3)
+        .ok_or(ExportError::UnsupportedMesh)?;
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().map(|index| index as u32).collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    Ok((positions, indices))
+}
+
+/// STL has no up axis, Blender reads it as Z up
+fn to_z_up([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [x, -z, y]
+}
+
+/// `(normal, corners)` of each triangle, Z up
+fn stl_facets(mesh: &Mesh) -> Result<Vec<([f32; 3], [[f32; 3]; 3])>, ExportError> {
+    let (positions, indices) = triangles(mesh)?;
+    Ok(indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let corners = [0, 1, 2].map(|i| to_z_up(positions[triangle[i] as usize]));
+            let [a, b, c] = corners.map(Vec3::from);
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            (normal.to_array(), corners)
+        })
+        .collect())
+}
+
+pub fn write_stl(mesh: &Mesh, writer: &mut impl Write) -> Result<(), ExportError> {
+    let triangles: Vec<Triangle> = stl_facets(mesh)?
+        .into_iter()
+        .map(|(normal, corners)| Triangle {
+            normal: Normal::new(normal),
+            vertices: corners.map(Vertex::new),
+        })
+        .collect();
+    stl_io::write_stl(writer, triangles.iter())?;
+    Ok(())
+}
+
+pub fn write_ascii_stl(
+    mesh: &Mesh,
+    name: &str,
+    writer: &mut impl Write,
+) -> Result<(), ExportError> {
+    writeln!(writer, "solid {}", name)?;
+    for (normal, corners) in stl_facets(mesh)? {
+        writeln!(
+            writer,
+            "  facet normal {:e} {:e} {:e}",
+            normal[0], normal[1], normal[2]
+        )?;
+        writeln!(writer, "    outer loop")?;
+        for [x, y, z] in corners {
+            writeln!(writer, "      vertex {:e} {:e} {:e}", x, y, z)?;
+        }
+        writeln!(writer, "    endloop")?;
+        writeln!(writer, "  endfacet")?;
+    }
+    writeln!(writer, "endsolid {}", name)?;
+    Ok(())
+}
+
+/// Writes `mesh` as a binary glTF with a single node called `name`, keeping its normals and
+/// UVs.
+pub fn write_glb(mesh: &Mesh, name: &str, writer: &mut impl Write) -> Result<(), ExportError> {
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+    let (positions, indices) = triangles(mesh)?;
+    let normals = mesh
+        .attribute(Mesh::ATTRIBUTE_NORMAL)
+        .and_then(VertexAttributeValues::as_float3);
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float32x2(uvs)) => Some(uvs.as_slice()),
+        _ => None,
+    };
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut attributes = serde_json::Map::new();
+    let mut push_view = |buffer: &mut Vec<u8>, bytes: Vec<u8>, target: u32| {
+        let offset = buffer.len();
+        buffer.extend_from_slice(&bytes);
+        // every view starts 4 byte aligned
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+        views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        views.len() - 1
+    };
+
+    let (min, max) = positions.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), position| {
+            (
+                min.min(Vec3::from(*position)),
+                max.max(Vec3::from(*position)),
+            )
+        },
+    );
+    let view = push_view(&mut buffer, float_bytes(positions), ARRAY_BUFFER);
+    attributes.insert("POSITION".into(), json!(accessors.len()));
+    accessors.push(json!({
+        "bufferView": view,
+        "componentType": FLOAT,
+        "count": positions.len(),
+        "type": "VEC3",
+        "min": min.to_array(),
+        "max": max.to_array(),
+    }));
+    if let Some(normals) = normals {
+        let view = push_view(&mut buffer, float_bytes(normals), ARRAY_BUFFER);
+        attributes.insert("NORMAL".into(), json!(accessors.len()));
+        accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": normals.len(),
+            "type": "VEC3",
+        }));
+    }
+    if let Some(uvs) = uvs {
+        let view = push_view(&mut buffer, float_bytes(uvs), ARRAY_BUFFER);
+        attributes.insert("TEXCOORD_0".into(), json!(accessors.len()));
+        accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": uvs.len(),
+            "type": "VEC2",
+        }));
+    }
+    let view = push_view(
+        &mut buffer,
+        indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect(),
+        ELEMENT_ARRAY_BUFFER,
+    );
+    let index_accessor = accessors.len();
+    accessors.push(json!({
+        "bufferView": view,
+        "componentType": UNSIGNED_INT,
+        "count": indices.len(),
+        "type": "SCALAR",
+    }));
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "hammerspace" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "name": name, "mesh": 0 }],
+        "meshes": [{
+            "name": name,
+            "primitives": [{ "attributes": attributes, "indices": index_accessor, "mode": 4 }],
+        }],
+        "buffers": [{ "byteLength": buffer.len() }],
+        "bufferViews": views,
+        "accessors": accessors,
+    });
+    let mut document = serde_json::to_vec(&document).map_err(std::io::Error::from)?;
+    document.resize(document.len().next_multiple_of(4), b' ');
+
+    // 12 byte header, then the JSON and binary chunks with 8 byte headers each
+    let length = 12 + 8 + document.len() + 8 + buffer.len();
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(length as u32).to_le_bytes())?;
+    writer.write_all(&(document.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&document)?;
+    writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+    writer.write_all(b"BIN\0")?;
+    writer.write_all(&buffer)?;
+    Ok(())
+}
+
+fn float_bytes<const N: usize>(values: &[[f32; N]]) -> Vec<u8> {
+    values
+        .iter()
+        .flatten()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
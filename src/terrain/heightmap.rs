@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use image::DynamicImage;
+
+/// A grid of heights for a `Terrain`, centered on the terrain entity in the xz plane.
+///
+/// Heights are kept between `0.0` and `1.0` and scaled by `height` in world space, the grid
+/// spans `size` with `width` samples along x and `depth` samples along z.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct Heightmap {
+    pub width: u32,
+    pub depth: u32,
+    /// samples in rows of `width`, starting at the -x -z corner
+    pub heights: Vec<f32>,
+    pub size: Vec2,
+    pub height: f32,
+}
+
+impl Heightmap {
+    pub fn new(width: u32, depth: u32, heights: Vec<f32>, size: Vec2, height: f32) -> Self {
+        assert_eq!(
+            heights.len(),
+            (width * depth) as usize,
+            "a {}x{} heightmap needs {} samples",
+            width,
+            depth,
+            width * depth
+        );
+        Self {
+            width,
+            depth,
+            heights,
+            size,
+            height,
+        }
+    }
+
+    /// reads the luminance of `image`, one sample per pixel with rows along z
+    pub fn from_image(image: &DynamicImage, size: Vec2, height: f32) -> Self {
+        let luma = image.to_luma16();
+        let heights = luma
+            .pixels()
+            .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+            .collect();
+        Self::new(luma.width(), luma.height(), heights, size, height)
+    }
+
+    /// a heightmap big enough to build a mesh from
+    pub fn is_valid(&self) -> bool {
+        self.width >= 2 && self.depth >= 2
+    }
+
+    /// world space distance between neighbouring samples
+    pub fn spacing(&self) -> Vec2 {
+        self.size
+            / UVec2::new(self.width - 1, self.depth - 1)
+                .max(UVec2::ONE)
+                .as_vec2()
+    }
+
+    /// normalised height of the sample at `x`, `z`, clamped to the grid
+    pub fn sample(&self, x: u32, z: u32) -> f32 {
+        let x = x.min(self.width - 1);
+        let z = z.min(self.depth - 1);
+        self.heights[(z * self.width + x) as usize]
+    }
+
+    /// position of the sample at `x`, `z` relative to the terrain entity
+    pub fn position(&self, x: u32, z: u32) -> Vec3 {
+        let corner = Vec2::new(x as f32, z as f32) * self.spacing() - self.size / 2.0;
+        Vec3::new(corner.x, self.sample(x, z) * self.height, corner.y)
+    }
+
+    /// normal of the surface at the sample at `x`, `z`, from its neighbours
+    pub fn normal(&self, x: u32, z: u32) -> Vec3 {
+        let spacing = self.spacing();
+        let (left, right) = (x.saturating_sub(1), (x + 1).min(self.width - 1));
+        let (back, front) = (z.saturating_sub(1), (z + 1).min(self.depth - 1));
+        let dx = (self.sample(right, z) - self.sample(left, z)) * self.height
+            / ((right - left).max(1) as f32 * spacing.x);
+        let dz = (self.sample(x, front) - self.sample(x, back)) * self.height
+            / ((front - back).max(1) as f32 * spacing.y);
+        Vec3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    /// Height of the surface at `point` relative to the terrain entity, interpolated between
+    /// samples, or `None` outside of the grid.
+    pub fn height_at(&self, point: Vec2) -> Option<f32> {
+        if !self.is_valid() {
+            return None;
+        }
+        let grid = (point + self.size / 2.0) / self.spacing();
+        let max = UVec2::new(self.width - 1, self.depth - 1).as_vec2();
+        if grid.cmplt(Vec2::ZERO).any() || grid.cmpgt(max).any() {
+            return None;
+        }
+        let cell = grid.floor().min(max - 1.0);
+        let t = grid - cell;
+        let (x, z) = (cell.x as u32, cell.y as u32);
+        let back = self.sample(x, z).lerp(self.sample(x + 1, z), t.x);
+        let front = self.sample(x, z + 1).lerp(self.sample(x + 1, z + 1), t.x);
+        Some(back.lerp(front, t.y) * self.height)
+    }
+}
+
+/// stretches `heights` to fill `0.0..=1.0`, flat heights all become `0.0`
+pub(crate) fn normalize_heights(heights: &mut [f32]) {
+    let (min, max) = heights
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), sample| {
+            (min.min(*sample), max.max(*sample))
+        });
+    if max > min {
+        for sample in heights.iter_mut() {
+            *sample = (*sample - min) / (max - min);
+        }
+    } else {
+        heights.fill(0.0);
+    }
+}
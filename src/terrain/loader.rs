@@ -0,0 +1,229 @@
+use std::fmt;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::assembler::loader::RonLoaderError;
+
+use super::{
+    heightmap::{normalize_heights, Heightmap},
+    noise::{generate, TerrainNoise},
+};
+
+/// Sample layout of a raw heightmap, as exported by World Machine or Gaea.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RawFormat {
+    /// little endian unsigned 16 bit samples, scaled to `0.0..=1.0`
+    R16,
+    /// little endian 32 bit floats in any unit, stretched so the lowest sample is at `0.0` and
+    /// the highest at `1.0`
+    R32,
+}
+
+impl RawFormat {
+    pub fn from_extension(path: &str) -> Option<Self> {
+        if path.ends_with(".r16") {
+            Some(RawFormat::R16)
+        } else if path.ends_with(".r32") {
+            Some(RawFormat::R32)
+        } else {
+            None
+        }
+    }
+
+    fn sample_size(self) -> usize {
+        match self {
+            RawFormat::R16 => 2,
+            RawFormat::R32 => 4,
+        }
+    }
+}
+
+/// How a heightmap file is placed in the world, set from a `.meta` file or
+/// `AssetServer::load_with_settings`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeightmapSettings {
+    /// world space extent of the heightmap along x and z
+    pub size: [f32; 2],
+    /// world space height of the highest sample
+    pub height: f32,
+    /// samples along x and z of a raw file, raw files are taken to be square without it, the
+    /// resolution of a `.terrain.ron` is set in the file
+    pub resolution: Option<[u32; 2]>,
+}
+
+impl Default for HeightmapSettings {
+    fn default() -> Self {
+        Self {
+            size: [256.0, 256.0],
+            height: 64.0,
+            resolution: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HeightmapLoaderError {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    /// a raw file whose length does not match its resolution
+    Size {
+        samples: usize,
+        resolution: UVec2,
+    },
+    /// invalid terrain noise settings
+    Noise(RonLoaderError),
+}
+
+impl fmt::Display for HeightmapLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeightmapLoaderError::Io(err) => write!(f, "could not read heightmap: {}", err),
+            HeightmapLoaderError::Image(err) => write!(f, "invalid heightmap image: {}", err),
+            HeightmapLoaderError::Size {
+                samples,
+                resolution,
+            } => write!(
+                f,
+                "raw heightmap has {} samples, which does not fit {}x{}",
+                samples, resolution.x, resolution.y
+            ),
+            HeightmapLoaderError::Noise(err) => write!(f, "invalid terrain noise: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for HeightmapLoaderError {}
+
+impl From<std::io::Error> for HeightmapLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        HeightmapLoaderError::Io(err)
+    }
+}
+
+impl From<image::ImageError> for HeightmapLoaderError {
+    fn from(err: image::ImageError) -> Self {
+        HeightmapLoaderError::Image(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for HeightmapLoaderError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        HeightmapLoaderError::Noise(err.into())
+    }
+}
+
+/// Reads the samples of a raw heightmap, `resolution` defaults to the largest square that
+/// fits the file.
+pub fn read_raw(
+    bytes: &[u8],
+    format: RawFormat,
+    resolution: Option<UVec2>,
+    size: Vec2,
+    height: f32,
+) -> Result<Heightmap, HeightmapLoaderError> {
+    let samples = bytes.len() / format.sample_size();
+    let resolution = resolution.unwrap_or_else(|| {
+        let side = (samples as f64).sqrt() as u32;
+        UVec2::splat(side)
+    });
+    if bytes.len() % format.sample_size() != 0 || samples != (resolution.x * resolution.y) as usize
+    {
+        return Err(HeightmapLoaderError::Size {
+            samples,
+            resolution,
+        });
+    }
+    let mut heights: Vec<f32> = match format {
+        RawFormat::R16 => bytes
+            .chunks_exact(2)
+            .map(|sample| u16::from_le_bytes([sample[0], sample[1]]) as f32 / u16::MAX as f32)
+            .collect(),
+        RawFormat::R32 => bytes
+            .chunks_exact(4)
+            .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))
+            .collect(),
+    };
+    if format == RawFormat::R32 {
+        normalize_heights(&mut heights);
+    }
+    Ok(Heightmap::new(
+        resolution.x,
+        resolution.y,
+        heights,
+        size,
+        height,
+    ))
+}
+
+/// Loads a `Heightmap` from an 8 or 16 bit grayscale `.height.png`, or a raw `.r16` or `.r32`.
+///
+/// Only files ending in `.height.png` are claimed, any other `.png` is loaded as an `Image`.
+#[derive(Default)]
+pub struct HeightmapLoader;
+
+impl AssetLoader for HeightmapLoader {
+    type Asset = Heightmap;
+    type Settings = HeightmapSettings;
+    type Error = HeightmapLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &HeightmapSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let size = Vec2::from(settings.size);
+        let path = load_context.path().to_string_lossy();
+        match RawFormat::from_extension(&path) {
+            Some(format) => read_raw(
+                &bytes,
+                format,
+                settings.resolution.map(UVec2::from),
+                size,
+                settings.height,
+            ),
+            None => {
+                let image = image::load_from_memory_with_format(&bytes, ImageFormat::Png)?;
+                Ok(Heightmap::from_image(&image, size, settings.height))
+            }
+        }
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["height.png", "r16", "r32"]
+    }
+}
+
+/// Generates a `Heightmap` from the `TerrainNoise` in a `.terrain.ron` file, placed in the world
+/// by the `HeightmapSettings`.
+#[derive(Default)]
+pub struct TerrainNoiseLoader;
+
+impl AssetLoader for TerrainNoiseLoader {
+    type Asset = Heightmap;
+    type Settings = HeightmapSettings;
+    type Error = HeightmapLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &HeightmapSettings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let noise: TerrainNoise = ron::de::from_bytes(&bytes)?;
+        Ok(generate(&noise, Vec2::from(settings.size), settings.height))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["terrain.ron"]
+    }
+}
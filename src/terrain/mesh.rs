@@ -0,0 +1,100 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use super::heightmap::Heightmap;
+
+/// how many chunks of `chunk_size` quads cover `heightmap` along x and z
+pub fn chunk_count(heightmap: &Heightmap, chunk_size: u32) -> UVec2 {
+    let quads = UVec2::new(heightmap.width - 1, heightmap.depth - 1);
+    let chunk_size = chunk_size.max(1);
+    (quads + chunk_size - 1) / chunk_size
+}
+
+/// samples from `start` to `end`, every `step` samples and always the last one
+fn chunk_samples(start: u32, end: u32, step: u32) -> Vec<u32> {
+    let mut samples: Vec<u32> = (start..end).step_by(step as usize).collect();
+    samples.push(end);
+    samples
+}
+
+/// Builds the mesh of chunk `coord`, keeping every `2^lod`th sample.
+///
+/// Normals are taken from the whole heightmap so they line up across chunks, UVs span the
+/// whole terrain. The edges are extended down by `skirt` to hide the cracks between chunks of
+/// different LODs.
+pub fn chunk_mesh(
+    heightmap: &Heightmap,
+    coord: UVec2,
+    chunk_size: u32,
+    lod: u32,
+    skirt: f32,
+) -> Mesh {
+    let chunk_size = chunk_size.max(1);
+    let step = (1u32 << lod.min(31)).min(chunk_size);
+    let start = coord * chunk_size;
+    let end = (start + chunk_size).min(UVec2::new(heightmap.width - 1, heightmap.depth - 1));
+    let xs = chunk_samples(start.x, end.x, step);
+    let zs = chunk_samples(start.y, end.y, step);
+    let row = xs.len() as u32;
+    let last = UVec2::new(heightmap.width - 1, heightmap.depth - 1)
+        .max(UVec2::ONE)
+        .as_vec2();
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    for &z in &zs {
+        for &x in &xs {
+            positions.push(heightmap.position(x, z).to_array());
+            normals.push(heightmap.normal(x, z).to_array());
+            uvs.push([x as f32 / last.x, z as f32 / last.y]);
+        }
+    }
+
+    let mut indices = Vec::new();
+    for z in 0..zs.len() as u32 - 1 {
+        for x in 0..row - 1 {
+            let corner = z * row + x;
+            indices.extend([corner, corner + row, corner + 1]);
+            indices.extend([corner + 1, corner + row, corner + row + 1]);
+        }
+    }
+
+    if skirt > 0.0 {
+        // the border walked clockwise seen from above, each edge gets a wall facing out
+        let columns = row - 1;
+        let rows = zs.len() as u32 - 1;
+        let border: Vec<u32> = (0..columns)
+            .chain((0..rows).map(|z| z * row + columns))
+            .chain((1..=columns).rev().map(|x| rows * row + x))
+            .chain((1..=rows).rev().map(|z| z * row))
+            .collect();
+        let skirt_start = positions.len() as u32;
+        for &index in &border {
+            let [x, y, z] = positions[index as usize];
+            positions.push([x, y - skirt, z]);
+            normals.push(normals[index as usize]);
+            uvs.push(uvs[index as usize]);
+        }
+        let count = border.len() as u32;
+        for i in 0..count {
+            let next = (i + 1) % count;
+            let (top, top_next) = (border[i as usize], border[next as usize]);
+            let (bottom, bottom_next) = (skirt_start + i, skirt_start + next);
+            indices.extend([top, top_next, bottom]);
+            indices.extend([bottom, top_next, bottom_next]);
+        }
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use components::TerrainHeightmap;
+use events::ExportTerrain;
+use heightmap::Heightmap;
+use loader::{HeightmapLoader, TerrainNoiseLoader};
+use systems::{export_terrains, load_terrain_heightmaps, spawn_terrain_chunks, update_terrain_lod};
+
+pub mod components;
+pub mod events;
+pub mod export;
+pub mod heightmap;
+pub mod loader;
+pub mod mesh;
+pub mod noise;
+pub mod systems;
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Heightmap>()
+            .init_asset_loader::<HeightmapLoader>()
+            .init_asset_loader::<TerrainNoiseLoader>()
+            .add_event::<ExportTerrain>()
+            .add_systems(Startup, setup_terrain_material)
+            .add_systems(
+                Update,
+                (
+                    load_terrain_heightmaps,
+                    spawn_terrain_chunks,
+                    update_terrain_lod,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, export_terrains.run_if(on_event::<ExportTerrain>))
+            .register_type::<TerrainHeightmap>();
+    }
+}
+
+/// material of terrains that were not given one
+pub const MATERIAL_TERRAIN: Handle<StandardMaterial> =
+    Handle::weak_from_u128(0x7e11_a2c4_5b3d_4f60_8e9a_1c2d_3f4b_5a6e);
+
+fn setup_terrain_material(mut materials: ResMut<Assets<StandardMaterial>>) {
+    materials.insert(
+        &MATERIAL_TERRAIN,
+        StandardMaterial {
+            base_color: Color::srgb(0.45, 0.5, 0.35),
+            perceptual_roughness: 0.9,
+            ..default()
+        },
+    );
+}
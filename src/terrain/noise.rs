@@ -0,0 +1,224 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::heightmap::{normalize_heights, Heightmap};
+
+/// Layered noise to generate a `Heightmap` from, loaded from a `.terrain.ron` file.
+///
+/// Frequencies count noise cells across the whole terrain, so the shape does not depend on the
+/// resolution or world size. The same seed gives the same heights on every machine.
+///
+/// ```ron
+/// (
+///     seed: 42,
+///     resolution: (257, 257),
+///     layers: [
+///         (kind: Fbm, frequency: 4.0, octaves: 6),
+///         (kind: Ridged, frequency: 2.0, amplitude: 0.5),
+///     ],
+///     warp: Some((frequency: 2.0, strength: 0.1)),
+/// )
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TerrainNoise {
+    pub seed: u64,
+    /// samples along x and z
+    pub resolution: [u32; 2],
+    pub layers: Vec<NoiseLayer>,
+    /// bends the coordinates every layer is sampled at
+    #[serde(default)]
+    pub warp: Option<DomainWarp>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoiseKind {
+    /// rolling hills
+    #[default]
+    Fbm,
+    /// sharp crests, for mountain ranges
+    Ridged,
+}
+
+/// one noise added to the terrain, summing `octaves` of gradient noise
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoiseLayer {
+    #[serde(default)]
+    pub kind: NoiseKind,
+    pub frequency: f32,
+    #[serde(default = "default_amplitude")]
+    pub amplitude: f32,
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+    /// how much the frequency grows with every octave
+    #[serde(default = "default_lacunarity")]
+    pub lacunarity: f32,
+    /// how much the amplitude shrinks with every octave
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DomainWarp {
+    pub frequency: f32,
+    /// how far coordinates are moved, as a fraction of the terrain
+    pub strength: f32,
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+}
+
+fn default_amplitude() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    4
+}
+
+fn default_lacunarity() -> f32 {
+    2.0
+}
+
+fn default_gain() -> f32 {
+    0.5
+}
+
+/// Samples `noise` into a heightmap spanning `size`, its heights scaled to fill
+/// `0.0..=1.0` before `height` is applied.
+pub fn generate(noise: &TerrainNoise, size: Vec2, height: f32) -> Heightmap {
+    let [width, depth] = noise.resolution;
+    let layers: Vec<Gradient> = (0..noise.layers.len() as u64)
+        .map(|layer| Gradient::new(noise.seed.wrapping_add(layer)))
+        .collect();
+    let warp = noise.warp.as_ref().map(|warp| {
+        let gradient = Gradient::new(noise.seed ^ 0x5741_5250);
+        (warp, gradient)
+    });
+    let last = UVec2::new(width, depth)
+        .saturating_sub(UVec2::ONE)
+        .max(UVec2::ONE)
+        .as_vec2();
+
+    let mut heights = Vec::with_capacity((width * depth) as usize);
+    for z in 0..depth {
+        for x in 0..width {
+            let mut point = Vec2::new(x as f32, z as f32) / last;
+            if let Some((warp, gradient)) = &warp {
+                // two far apart samples of the same noise give the offset along x and z
+                let offset = Vec2::new(
+                    gradient.fbm(point * warp.frequency, warp.octaves, 2.0, 0.5),
+                    gradient.fbm(
+                        point * warp.frequency + Vec2::new(37.2, 91.7),
+                        warp.octaves,
+                        2.0,
+                        0.5,
+                    ),
+                );
+                point += offset * warp.strength;
+            }
+            let sample = noise
+                .layers
+                .iter()
+                .zip(&layers)
+                .map(|(layer, gradient)| {
+                    let point = point * layer.frequency;
+                    let value = match layer.kind {
+                        NoiseKind::Fbm => {
+                            gradient.fbm(point, layer.octaves, layer.lacunarity, layer.gain)
+                        }
+                        NoiseKind::Ridged => {
+                            gradient.ridged(point, layer.octaves, layer.lacunarity, layer.gain)
+                        }
+                    };
+                    value * layer.amplitude
+                })
+                .sum::<f32>();
+            heights.push(sample);
+        }
+    }
+
+    normalize_heights(&mut heights);
+    Heightmap::new(width, depth, heights, size, height)
+}
+
+/// 2D gradient noise over a permutation shuffled from a seed
+struct Gradient {
+    permutation: [u8; 512],
+}
+
+impl Gradient {
+    fn new(seed: u64) -> Self {
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+        let mut state = seed;
+        for i in (1..256).rev() {
+            let j = (split_mix(&mut state) % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+        Self {
+            permutation: std::array::from_fn(|i| table[i & 255]),
+        }
+    }
+
+    fn hash(&self, x: i32, z: i32) -> u8 {
+        let x = (x & 255) as usize;
+        let z = (z & 255) as usize;
+        self.permutation[self.permutation[x] as usize + z]
+    }
+
+    /// noise at `point`, roughly within `-1.0..=1.0`
+    fn get(&self, point: Vec2) -> f32 {
+        let cell = point.floor();
+        let local = point - cell;
+        let (x, z) = (cell.x as i32, cell.y as i32);
+        let corner = |dx: i32, dz: i32| {
+            let gradient = match self.hash(x + dx, z + dz) & 7 {
+                0 => Vec2::new(1.0, 1.0),
+                1 => Vec2::new(-1.0, 1.0),
+                2 => Vec2::new(1.0, -1.0),
+                3 => Vec2::new(-1.0, -1.0),
+                4 => Vec2::new(1.0, 0.0),
+                5 => Vec2::new(-1.0, 0.0),
+                6 => Vec2::new(0.0, 1.0),
+                _ => Vec2::new(0.0, -1.0),
+            };
+            gradient.dot(local - Vec2::new(dx as f32, dz as f32))
+        };
+        let fade = local * local * local * (local * (local * 6.0 - 15.0) + 10.0);
+        let back = corner(0, 0).lerp(corner(1, 0), fade.x);
+        let front = corner(0, 1).lerp(corner(1, 1), fade.x);
+        back.lerp(front, fade.y)
+    }
+
+    /// fractal Brownian motion, octaves of noise at growing frequencies summed together
+    fn fbm(&self, point: Vec2, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        let (mut sum, mut amplitude, mut frequency) = (0.0, 1.0, 1.0);
+        for _ in 0..octaves {
+            sum += self.get(point * frequency) * amplitude;
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+        sum
+    }
+
+    /// octaves folded around zero so valleys become crests, each octave weighted by the last
+    fn ridged(&self, point: Vec2, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+        let (mut sum, mut amplitude, mut frequency, mut weight) = (0.0, 1.0, 1.0, 1.0);
+        for _ in 0..octaves {
+            let ridge = 1.0 - self.get(point * frequency).abs();
+            let ridge = ridge * ridge * weight;
+            sum += ridge * amplitude;
+            weight = ridge.clamp(0.0, 1.0);
+            amplitude *= gain;
+            frequency *= lacunarity;
+        }
+        sum
+    }
+}
+
+/// the SplitMix64 generator, used over `rand` so the output never changes between versions
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,216 @@
+use std::{fs::File, io::BufWriter};
+
+use bevy::{prelude::*, render::primitives::Aabb, utils::HashSet};
+
+use crate::assembler::components::LevelTerrain;
+
+use super::{
+    components::{Terrain, TerrainChunk, TerrainHeightmap},
+    events::ExportTerrain,
+    export::{export_mesh, heightmap_mesh, ExportError},
+    heightmap::Heightmap,
+    loader::HeightmapSettings,
+    mesh::{chunk_count, chunk_mesh},
+    MATERIAL_TERRAIN,
+};
+
+/// loads the file of new or changed `TerrainHeightmap`s into the `Terrain` of the entity
+pub fn load_terrain_heightmaps(
+    mut commands: Commands,
+    mut source_q: Query<
+        (Entity, &TerrainHeightmap, Option<&mut Terrain>),
+        Changed<TerrainHeightmap>,
+    >,
+    server: Res<AssetServer>,
+) {
+    for (entity, source, terrain) in source_q.iter_mut() {
+        let (size, height) = (source.size.to_array(), source.height);
+        let heightmap =
+            server.load_with_settings(&source.path, move |settings: &mut HeightmapSettings| {
+                settings.size = size;
+                settings.height = height;
+            });
+        let material = match source.material.as_str() {
+            "" => MATERIAL_TERRAIN,
+            path => server.load(path),
+        };
+        match terrain {
+            Some(mut terrain) => {
+                terrain.heightmap = heightmap;
+                terrain.material = material;
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(Terrain::new(heightmap).with_material(material));
+            }
+        }
+    }
+}
+
+/// (re)spawns the chunks of terrains that were added or changed, or whose heightmap was
+pub fn spawn_terrain_chunks(
+    mut commands: Commands,
+    mut heightmap_ev: EventReader<AssetEvent<Heightmap>>,
+    terrain_q: Query<(Entity, Ref<Terrain>, Option<&Children>)>,
+    chunk_q: Query<(), With<TerrainChunk>>,
+    heightmaps: Res<Assets<Heightmap>>,
+) {
+    let updated: HashSet<AssetId<Heightmap>> = heightmap_ev
+        .read()
+        .filter_map(|ev| match ev {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, terrain, children) in terrain_q.iter() {
+        if !terrain.is_changed() && !updated.contains(&terrain.heightmap.id()) {
+            continue;
+        }
+        let Some(heightmap) = heightmaps.get(&terrain.heightmap) else {
+            continue;
+        };
+        for child in children.into_iter().flatten() {
+            if chunk_q.contains(*child) {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+        if !heightmap.is_valid() {
+            warn!("heightmap of terrain {} has too few samples", entity);
+            continue;
+        }
+
+        let chunk_size = terrain.chunk_size.max(1);
+        let count = chunk_count(heightmap, chunk_size);
+        commands.entity(entity).with_children(|parent| {
+            for z in 0..count.y {
+                for x in 0..count.x {
+                    let start = UVec2::new(x, z) * chunk_size;
+                    let end = (start + chunk_size)
+                        .min(UVec2::new(heightmap.width - 1, heightmap.depth - 1));
+                    let center = (heightmap.position(start.x, start.y)
+                        + heightmap.position(end.x, end.y))
+                        / 2.0;
+                    parent.spawn((
+                        Name::new(format!("terrain chunk {} {}", x, z)),
+                        TerrainChunk {
+                            coord: UVec2::new(x, z),
+                            lod: None,
+                            center,
+                            meshes: Vec::new(),
+                        },
+                        LevelTerrain,
+                        MeshMaterial3d(terrain.material.clone()),
+                        Transform::default(),
+                        Visibility::default(),
+                    ));
+                }
+            }
+        });
+    }
+}
+
+/// swaps each chunk to the mesh for its distance from the closest active camera
+pub fn update_terrain_lod(
+    mut commands: Commands,
+    mut chunk_q: Query<(Entity, &mut TerrainChunk, &Parent)>,
+    terrain_q: Query<(&Terrain, &GlobalTransform)>,
+    cam_q: Query<(&GlobalTransform, &Camera)>,
+    heightmaps: Res<Assets<Heightmap>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let cameras: Vec<Vec3> = cam_q
+        .iter()
+        .filter(|(_, cam)| cam.is_active)
+        .map(|(transform, _)| transform.translation())
+        .collect();
+
+    for (entity, mut chunk, parent) in chunk_q.iter_mut() {
+        let Ok((terrain, transform)) = terrain_q.get(parent.get()) else {
+            continue;
+        };
+        let Some(heightmap) = heightmaps.get(&terrain.heightmap) else {
+            continue;
+        };
+        let center = transform.transform_point(chunk.center);
+        let distance = cameras
+            .iter()
+            .map(|camera| camera.distance(center))
+            .reduce(f32::min)
+            .unwrap_or(0.0);
+        let lod = terrain.lod_at(distance);
+        if chunk.lod == Some(lod) {
+            continue;
+        }
+
+        if chunk.meshes.len() <= lod as usize {
+            chunk.meshes.resize(lod as usize + 1, None);
+        }
+        let coord = chunk.coord;
+        let mesh = chunk.meshes[lod as usize]
+            .get_or_insert_with(|| {
+                meshes.add(chunk_mesh(
+                    heightmap,
+                    coord,
+                    terrain.chunk_size,
+                    lod,
+                    terrain.skirt,
+                ))
+            })
+            .clone();
+        chunk.lod = Some(lod);
+        // the bounds are recomputed for the new mesh
+        commands
+            .entity(entity)
+            .insert(Mesh3d(mesh))
+            .remove::<Aabb>();
+    }
+}
+
+/// writes out the terrain asked for by each `ExportTerrain`
+#[allow(clippy::too_many_arguments)]
+pub fn export_terrains(
+    mut export_ev: EventReader<ExportTerrain>,
+    terrain_q: Query<&Terrain>,
+    level_terrain_q: Query<(&Mesh3d, &GlobalTransform), With<LevelTerrain>>,
+    children_q: Query<&Children>,
+    global_q: Query<&GlobalTransform>,
+    name_q: Query<&Name>,
+    heightmaps: Res<Assets<Heightmap>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for ev in export_ev.read() {
+        let mesh = match terrain_q.get(ev.entity) {
+            Ok(terrain) => heightmaps
+                .get(&terrain.heightmap)
+                .filter(|heightmap| heightmap.is_valid())
+                .map(heightmap_mesh),
+            Err(_) => {
+                let root = global_q.get(ev.entity).copied().unwrap_or_default();
+                std::iter::once(ev.entity)
+                    .chain(children_q.iter_descendants(ev.entity))
+                    .filter_map(|entity| level_terrain_q.get(entity).ok())
+                    .filter_map(|(mesh, transform)| {
+                        let mesh = meshes.get(&mesh.0)?;
+                        Some(mesh.clone().transformed_by(transform.reparented_to(&root)))
+                    })
+                    .reduce(|mut merged, mesh| {
+                        merged.merge(&mesh);
+                        merged
+                    })
+            }
+        };
+        let name = name_q.get(ev.entity).map(Name::as_str).unwrap_or("terrain");
+        let result = mesh.ok_or(ExportError::NoMesh).and_then(|mesh| {
+            let mut writer = BufWriter::new(File::create(&ev.path)?);
+            export_mesh(&mesh, name, ev.format, &mut writer)
+        });
+        match result {
+            Ok(()) => info!("exported terrain {} to {}", ev.entity, ev.path.display()),
+            Err(err) => error!("could not export terrain {}: {}", ev.entity, err),
+        }
+    }
+}
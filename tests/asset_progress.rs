@@ -0,0 +1,200 @@
+use std::{path::Path, thread, time::Duration};
+
+use bevy::{
+    asset::io::{memory::Dir, memory::MemoryAssetReader, AssetSource, AssetSourceId},
+    prelude::*,
+    state::app::StatesPlugin,
+};
+use blenvy::GameWorldTag;
+use hammerspace::assembler::{
+    events::{AssetsFailedEvent, PostProgresssEvent},
+    resources::{AssetEntry, AssetLoadReport, ImageAssets, SessionAssets},
+    AssetLoadState, LoaderPlugin,
+};
+
+/// a 1x1 transparent png
+const PIXEL: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0b, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60, 0x00, 0x02, 0x00,
+    0x00, 0x05, 0x00, 0x01, 0x7a, 0x5e, 0xab, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44,
+    0xae, 0x42, 0x60, 0x82,
+];
+
+#[derive(Resource, Default)]
+struct Recorded {
+    progress: Vec<(u32, u32)>,
+    failed: usize,
+}
+
+fn record(
+    mut recorded: ResMut<Recorded>,
+    mut progress_ev: EventReader<PostProgresssEvent>,
+    mut failed_ev: EventReader<AssetsFailedEvent>,
+) {
+    recorded
+        .progress
+        .extend(progress_ev.read().map(|ev| (ev.1, ev.2)));
+    recorded.failed += failed_ev.read().count();
+}
+
+fn test_app(files: &[&str]) -> App {
+    let dir = Dir::default();
+    for file in files {
+        dir.insert_asset(Path::new(file), PIXEL);
+    }
+
+    let mut app = App::new();
+    app.register_asset_source(
+        AssetSourceId::Default,
+        AssetSource::build().with_reader(move || Box::new(MemoryAssetReader { root: dir.clone() })),
+    )
+    .add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        ImagePlugin::default(),
+        StatesPlugin,
+        LoaderPlugin,
+    ))
+    .init_resource::<Recorded>()
+    .add_systems(Last, record);
+    app.finish();
+    app.cleanup();
+
+    app.world_mut().spawn(GameWorldTag);
+    app.world_mut()
+        .resource_mut::<NextState<AssetLoadState>>()
+        .set(AssetLoadState::Loading);
+    app
+}
+
+fn run_until_settled(app: &mut App) -> AssetLoadState {
+    for _ in 0..2000 {
+        app.update();
+        let state = *app.world().resource::<State<AssetLoadState>>().get();
+        if matches!(state, AssetLoadState::Loaded | AssetLoadState::Failed) {
+            return state;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("assets never settled");
+}
+
+#[test]
+fn progress_reaches_total_exactly_once() {
+    let mut app = test_app(&["images/a.png", "images/b.png", "images/c.png"]);
+    app.insert_resource(
+        SessionAssets::new()
+            .with_image("a", "a")
+            .with_image("b", "b")
+            .with_image("c", "c"),
+    );
+
+    assert_eq!(run_until_settled(&mut app), AssetLoadState::Loaded);
+    for _ in 0..10 {
+        app.update();
+    }
+
+    let recorded = app.world().resource::<Recorded>();
+    assert_eq!(recorded.progress, vec![(1, 3), (2, 3), (3, 3)]);
+    assert_eq!(recorded.failed, 0);
+}
+
+#[test]
+fn missing_optional_asset_still_completes() {
+    let mut app = test_app(&["images/a.png"]);
+    app.insert_resource(
+        SessionAssets::new()
+            .with_image("a", "a")
+            .with_image("missing", AssetEntry::new("missing").optional()),
+    );
+
+    assert_eq!(run_until_settled(&mut app), AssetLoadState::Loaded);
+
+    let recorded = app.world().resource::<Recorded>();
+    assert_eq!(recorded.progress.last(), Some(&(2, 2)));
+    assert_eq!(app.world().resource::<AssetLoadReport>().failures.len(), 1);
+}
+
+#[test]
+fn missing_required_asset_fails() {
+    let mut app = test_app(&["images/a.png"]);
+    app.insert_resource(
+        SessionAssets::new()
+            .with_image("a", "a")
+            .with_image("missing", "missing"),
+    );
+
+    assert_eq!(run_until_settled(&mut app), AssetLoadState::Failed);
+    app.update();
+
+    let recorded = app.world().resource::<Recorded>();
+    assert_eq!(recorded.failed, 1);
+    let report = app.world().resource::<AssetLoadReport>();
+    assert_eq!(report.failures[0].path, "images/missing.png");
+}
+
+#[test]
+fn sprite_sheets_keep_their_own_layers() {
+    let layers = [
+        "uv_canvas",
+        "occlusion",
+        "normal_sheet",
+        "uv_sheet",
+        "volume",
+    ];
+    let files: Vec<String> = ["hero", "villain"]
+        .iter()
+        .flat_map(|sheet| {
+            layers
+                .iter()
+                .map(move |layer| format!("images/sprites/{}/{}.png", sheet, layer))
+        })
+        .collect();
+    let mut app = test_app(&files.iter().map(String::as_str).collect::<Vec<_>>());
+    app.insert_resource(
+        SessionAssets::new()
+            .with_sprite_sheet("hero", "hero")
+            .with_sprite_sheet("villain", "villain"),
+    );
+
+    assert_eq!(run_until_settled(&mut app), AssetLoadState::Loaded);
+
+    let images = &app.world().resource::<ImageAssets>().0;
+    assert_eq!(images.len(), 10);
+    let server = app.world().resource::<AssetServer>();
+    for sheet in ["hero", "villain"] {
+        let sprite = &images[&format!("{}/uv_color", sheet)];
+        let path = server.get_path(sprite.image.id()).unwrap();
+        assert_eq!(
+            path.path().to_str(),
+            Some(format!("images/sprites/{}/uv_canvas.png", sheet).as_str())
+        );
+    }
+}
+
+#[test]
+fn levels_prepared_together_add_to_the_progress() {
+    let mut app = test_app(&["images/a.png", "images/b.png", "images/c.png"]);
+    // a second level prepared next to the first one has its own world
+    app.world_mut().spawn(GameWorldTag);
+    app.insert_resource(SessionAssets::new().with_image("a", "a"));
+    app.update();
+
+    // the manifest of the second level is merged in, like `resolve_level_manifest` does
+    let merged = app
+        .world_mut()
+        .remove_resource::<SessionAssets>()
+        .unwrap()
+        .with_image("b", "b")
+        .with_image("c", "c");
+    app.insert_resource(merged);
+
+    assert_eq!(run_until_settled(&mut app), AssetLoadState::Loaded);
+
+    let recorded = app.world().resource::<Recorded>();
+    assert_eq!(recorded.progress.last(), Some(&(3, 3)));
+    // nothing is counted twice or started over
+    let done: Vec<u32> = recorded.progress.iter().map(|(done, _)| *done).collect();
+    assert!(done.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", done);
+}
@@ -0,0 +1,168 @@
+use std::{path::Path, thread, time::Duration};
+
+use bevy::{
+    asset::io::{memory::Dir, memory::MemoryAssetReader, AssetSource, AssetSourceId},
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+use hammerspace::ai_controller::{
+    behavior::{
+        Action, BehaviorActions, BehaviorMemory, BehaviorTree, Blackboard, BlackboardValue, Status,
+    },
+    components::{AiBehavior, BehaviorRunner},
+    events::DialogueRequested,
+    AiControllerPlugin,
+};
+
+const GREETER: &str = r#"(
+    root: Sequence([
+        Wait(1.0),
+        Action(PlayDialogue("hello")),
+    ]),
+)"#;
+
+/// records the actions it is asked to run, finishing each one after `ticks` ticks
+#[derive(Default)]
+struct FakeActions {
+    ticks: u32,
+    ran: Vec<(Action, bool)>,
+    halted: Vec<Action>,
+}
+
+impl BehaviorActions for FakeActions {
+    fn run(&mut self, action: &Action, first: bool, _blackboard: &mut Blackboard) -> Status {
+        self.ran.push((action.clone(), first));
+        let count = self.ran.iter().filter(|(ran, _)| ran == action).count() as u32;
+        if count > self.ticks {
+            Status::Success
+        } else {
+            Status::Running
+        }
+    }
+
+    fn halt(&mut self, action: &Action) {
+        self.halted.push(action.clone());
+    }
+}
+
+#[test]
+fn selector_falls_back_and_blackboard_is_shared() {
+    let tree: BehaviorTree = ron::from_str(
+        r#"(
+            root: Sequence([
+                Selector([
+                    Check("alerted"),
+                    Set("alerted", Bool(true)),
+                ]),
+                Timeout(seconds: 5.0, child: Action(MoveToMarker("post"))),
+            ]),
+        )"#,
+    )
+    .unwrap();
+    let mut memory = BehaviorMemory::default();
+    let mut blackboard = Blackboard::default();
+    let mut actions = FakeActions {
+        ticks: 1,
+        ..default()
+    };
+
+    assert_eq!(
+        tree.tick(&mut memory, &mut blackboard, 0.0, &mut actions),
+        Status::Running
+    );
+    assert_eq!(
+        blackboard.get("alerted"),
+        Some(&BlackboardValue::Bool(true))
+    );
+    assert_eq!(
+        tree.tick(&mut memory, &mut blackboard, 0.5, &mut actions),
+        Status::Success
+    );
+    let marker = Action::MoveToMarker("post".to_string());
+    assert_eq!(actions.ran, vec![(marker.clone(), true), (marker, false)]);
+}
+
+#[test]
+fn timeout_fails_slow_actions() {
+    let tree: BehaviorTree =
+        ron::from_str(r#"(root: Timeout(seconds: 1.0, child: Action(PlayDialogue("hi"))))"#)
+            .unwrap();
+    let mut memory = BehaviorMemory::default();
+    let mut blackboard = Blackboard::default();
+    let mut actions = FakeActions {
+        ticks: 10,
+        ..default()
+    };
+
+    for now in [0.0, 0.25, 0.5, 0.75] {
+        assert_eq!(
+            tree.tick(&mut memory, &mut blackboard, now, &mut actions),
+            Status::Running
+        );
+    }
+    assert!(actions.halted.is_empty());
+    assert_eq!(
+        tree.tick(&mut memory, &mut blackboard, 1.0, &mut actions),
+        Status::Failure
+    );
+    // the action was still running, so it is told to stop
+    assert_eq!(actions.halted, vec![Action::PlayDialogue("hi".to_string())]);
+    // the tree starts over, so the action is started again
+    tree.tick(&mut memory, &mut blackboard, 1.25, &mut actions);
+    assert!(actions.ran.last().unwrap().1);
+}
+
+#[test]
+fn tick_system_waits_on_the_clock() {
+    let dir = Dir::default();
+    dir.insert_asset(Path::new("greeter.bt.ron"), GREETER.as_bytes());
+
+    let mut app = App::new();
+    app.register_asset_source(
+        AssetSourceId::Default,
+        AssetSource::build().with_reader(move || Box::new(MemoryAssetReader { root: dir.clone() })),
+    )
+    .add_plugins((MinimalPlugins, AssetPlugin::default(), AiControllerPlugin))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+    app.finish();
+    app.cleanup();
+    let npc = app
+        .world_mut()
+        .spawn(AiBehavior::new("greeter.bt.ron"))
+        .id();
+
+    let dialogues = |app: &mut App| {
+        app.world_mut()
+            .resource_mut::<Events<DialogueRequested>>()
+            .drain()
+            .map(|ev| (ev.entity, ev.dialogue))
+            .collect::<Vec<_>>()
+    };
+
+    // the first tick happens once the tree has loaded, starting the wait
+    let mut loaded = false;
+    for _ in 0..2000 {
+        app.update();
+        if app
+            .world()
+            .get::<BehaviorRunner>(npc)
+            .unwrap()
+            .status
+            .is_some()
+        {
+            loaded = true;
+            break;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert!(loaded, "behavior tree never loaded");
+
+    for _ in 0..3 {
+        app.update();
+        assert!(dialogues(&mut app).is_empty());
+    }
+    app.update();
+    assert_eq!(dialogues(&mut app), vec![(npc, "hello".to_string())]);
+}
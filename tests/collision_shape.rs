@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use hammerspace::assembler::components::CollisionShape;
+
+#[test]
+fn convex_colliders_keep_their_hull() {
+    // a wedge, with a vertex in the middle of it that is not part of the hull
+    let points = [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 2.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(2.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 2.0),
+        Vec3::new(0.5, 0.5, 0.5),
+    ];
+    let shape = CollisionShape::from_points(&points, true).unwrap();
+    let CollisionShape::ConvexHull { outline, .. } = &shape else {
+        panic!("expected a hull");
+    };
+    assert_eq!(outline.len(), 3);
+    assert_eq!(shape.bounds(), (Vec3::ZERO, Vec3::new(2.0, 1.0, 2.0)));
+
+    let transform = GlobalTransform::IDENTITY;
+    assert!(shape.contains(&transform, Vec3::new(0.5, 0.5, 0.5)));
+    // inside the bounds, outside the wedge
+    assert!(!shape.contains(&transform, Vec3::new(1.5, 0.5, 1.5)));
+    assert!(shape.blocks(
+        &transform,
+        Vec3::new(-1.0, 0.5, 0.5),
+        Vec3::new(3.0, 0.5, 0.5)
+    ));
+    assert!(!shape.blocks(
+        &transform,
+        Vec3::new(1.5, 0.5, 3.0),
+        Vec3::new(3.0, 0.5, 1.5)
+    ));
+    assert!(!shape.blocks(
+        &transform,
+        Vec3::new(1.2, 0.5, 1.2),
+        Vec3::new(1.8, 0.5, 1.8)
+    ));
+
+    let cuboid = CollisionShape::from_points(&points, false).unwrap();
+    assert!(cuboid.contains(&transform, Vec3::new(1.5, 0.5, 1.5)));
+    assert!(CollisionShape::from_points(&[], true).is_none());
+}
@@ -0,0 +1,115 @@
+use std::{path::Path, thread, time::Duration};
+
+use bevy::{
+    asset::{
+        io::{memory::Dir, memory::MemoryAssetReader, AssetSource, AssetSourceId},
+        LoadState,
+    },
+    prelude::*,
+};
+use hammerspace::assembler::{
+    loader::{LevelManifest, ManifestLoader},
+    resources::AssetCategory,
+};
+
+const RON: &str = r#"(
+    scenes: {
+        "hub": (path: "levels/hub"),
+    },
+    sprite_sheets: {
+        "hero": (path: "hero", priority: 10),
+    },
+)"#;
+
+const JSON: &str = r#"{
+  "images": {
+    "splash": { "path": "splash", "format": "jpg", "optional": true }
+  }
+}"#;
+
+const MALFORMED: &str = r#"(
+    scenes: {
+        "hub": (path: "levels/hub", priority: high),
+    },
+)"#;
+
+fn test_app() -> App {
+    let dir = Dir::default();
+    for (file, contents) in [
+        ("hub.manifest.ron", RON),
+        ("hub.manifest.json", JSON),
+        ("broken.manifest.ron", MALFORMED),
+    ] {
+        dir.insert_asset(Path::new(file), contents.as_bytes());
+    }
+
+    let mut app = App::new();
+    app.register_asset_source(
+        AssetSourceId::Default,
+        AssetSource::build().with_reader(move || Box::new(MemoryAssetReader { root: dir.clone() })),
+    )
+    .add_plugins((MinimalPlugins, AssetPlugin::default()))
+    .init_asset::<LevelManifest>()
+    .init_asset_loader::<ManifestLoader>();
+    app.finish();
+    app.cleanup();
+    app
+}
+
+fn load(app: &mut App, path: &'static str) -> (Handle<LevelManifest>, LoadState) {
+    let handle = app.world().resource::<AssetServer>().load(path);
+    for _ in 0..2000 {
+        app.update();
+        let state = app.world().resource::<AssetServer>().load_state(&handle);
+        if matches!(state, LoadState::Loaded | LoadState::Failed(_)) {
+            return (handle, state);
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("{} never loaded", path);
+}
+
+#[test]
+fn ron_manifest_loads() {
+    let mut app = test_app();
+    let (handle, state) = load(&mut app, "hub.manifest.ron");
+
+    assert!(matches!(state, LoadState::Loaded));
+    let manifest = app.world().resource::<Assets<LevelManifest>>();
+    let assets = &manifest.get(&handle).unwrap().0;
+    assert_eq!(
+        assets.category(AssetCategory::Scene)["hub"].path,
+        "levels/hub"
+    );
+    assert_eq!(
+        assets.category(AssetCategory::SpriteSheet)["hero"].priority,
+        10
+    );
+}
+
+#[test]
+fn json_manifest_loads() {
+    let mut app = test_app();
+    let (handle, state) = load(&mut app, "hub.manifest.json");
+
+    assert!(matches!(state, LoadState::Loaded));
+    let manifest = app.world().resource::<Assets<LevelManifest>>();
+    let splash = &manifest.get(&handle).unwrap().0.images["splash"];
+    assert_eq!(splash.format.as_deref(), Some("jpg"));
+    assert!(splash.optional);
+}
+
+#[test]
+fn malformed_manifest_reports_where() {
+    let mut app = test_app();
+    let (_, state) = load(&mut app, "broken.manifest.ron");
+
+    let LoadState::Failed(err) = state else {
+        panic!("malformed manifest loaded");
+    };
+    assert!(
+        err.to_string().contains("line 3, column 47"),
+        "unexpected error: {}",
+        err
+    );
+}
@@ -0,0 +1,424 @@
+#![cfg(feature = "pathfind")]
+
+use std::{thread, time::Duration};
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use hammerspace::{
+    assembler::components::CollisionShape,
+    pathfind::{
+        components::{NavLink, Navigator, ObstacleFootprint, PathNodes},
+        events::{PathBlocked, PathCompleted, PathEvent, PathNotFound, TraverseLink},
+        generate::generate_ground,
+        resources::{LevelNavMesh, NavGround, NavMeshGeneration, PathfindSettings},
+        systems::{collect_nav_links, derive_obstacle_shapes, follow_path, get_path, poll_paths},
+        Obstacle,
+    },
+};
+use vleue_navigator::{prelude::PrimitiveObstacle, NavMesh};
+
+#[test]
+fn off_center_obstacles_keep_their_footprint_in_place() {
+    let mut app = App::new();
+    app.add_systems(Update, derive_obstacle_shapes);
+    // a door hinged at its edge, spanning x 0..2
+    let door = app
+        .world_mut()
+        .spawn((
+            Obstacle::Live,
+            CollisionShape::Cuboid {
+                center: Vec3::new(1.0, 1.0, 0.0),
+                half_extents: Vec3::new(1.0, 1.0, 0.1),
+            },
+        ))
+        .id();
+    let crate_ = app
+        .world_mut()
+        .spawn((
+            Obstacle::Cached,
+            CollisionShape::Cuboid {
+                center: Vec3::ZERO,
+                half_extents: Vec3::splat(0.5),
+            },
+        ))
+        .id();
+    app.update();
+    app.update();
+
+    let footprint = app.world().get::<ObstacleFootprint>(door).unwrap().0;
+    assert!(app.world().get::<PrimitiveObstacle>(door).is_none());
+    assert_eq!(app.world().get::<Parent>(footprint).unwrap().get(), door);
+    assert_eq!(
+        app.world().get::<Transform>(footprint).unwrap().translation,
+        Vec3::new(1.0, 0.0, 0.0)
+    );
+    assert_eq!(
+        app.world().get::<Obstacle>(footprint),
+        Some(&Obstacle::Live)
+    );
+    match app.world().get::<PrimitiveObstacle>(footprint) {
+        Some(PrimitiveObstacle::Rectangle(rectangle)) => {
+            assert_eq!(rectangle.size(), Vec2::new(2.0, 0.2))
+        }
+        _ => panic!("expected a rectangle footprint"),
+    }
+
+    assert!(app.world().get::<ObstacleFootprint>(crate_).is_none());
+    assert!(matches!(
+        app.world().get::<PrimitiveObstacle>(crate_),
+        Some(PrimitiveObstacle::Rectangle(_))
+    ));
+}
+
+#[test]
+fn nav_links_wait_for_their_target() {
+    let mut app = App::new();
+    app.init_resource::<LevelNavMesh>()
+        .add_systems(Update, collect_nav_links);
+    app.world_mut().spawn((
+        NavLink {
+            to: "ledge".to_string(),
+            bidirectional: false,
+            ..default()
+        },
+        GlobalTransform::from_translation(Vec3::ZERO),
+    ));
+    app.update();
+    assert!(app.world().resource::<LevelNavMesh>().links.is_empty());
+
+    // the target is streamed in later
+    let ledge = app
+        .world_mut()
+        .spawn((
+            Name::new("ledge"),
+            GlobalTransform::from_translation(Vec3::new(0.0, 2.0, 1.0)),
+        ))
+        .id();
+    app.update();
+    let links = app.world().resource::<LevelNavMesh>().links.clone();
+    assert_eq!(links.len(), 1);
+    assert_eq!(links[0].to, Vec3::new(0.0, 2.0, 1.0));
+
+    // and moved afterwards
+    *app.world_mut().get_mut::<GlobalTransform>(ledge).unwrap() =
+        GlobalTransform::from_translation(Vec3::new(0.0, 3.0, 1.0));
+    app.update();
+    let links = app.world().resource::<LevelNavMesh>().links.clone();
+    assert_eq!(links[0].to, Vec3::new(0.0, 3.0, 1.0));
+}
+
+/// Two triangles from `from` to `to`, sloping along x, facing up. Quads are kept 3 deep so the
+/// diagonal misses the cell centers.
+fn quad(from: Vec3, to: Vec3) -> [[Vec3; 3]; 2] {
+    let (c00, c01) = (from, from.with_z(to.z));
+    let (c10, c11) = (to.with_z(from.z), to);
+    [[c00, c01, c11], [c00, c11, c10]]
+}
+
+fn coarse() -> NavMeshGeneration {
+    NavMeshGeneration {
+        cell_size: 1.0,
+        ..default()
+    }
+}
+
+#[test]
+fn flat_ground_is_walkable() {
+    let ground = generate_ground(&quad(Vec3::ZERO, Vec3::new(4.0, 0.0, 3.0)), &coarse());
+
+    // the 4x3 cells are merged into a single rectangle
+    assert_eq!(ground.0.len(), 2);
+    assert!(ground.0.iter().flatten().all(|point| point.y == 0.0));
+}
+
+#[test]
+fn steep_ramps_are_not_walkable() {
+    let mut triangles = quad(Vec3::ZERO, Vec3::new(2.0, 0.0, 3.0)).to_vec();
+    // rises 4 over 2, far steeper than the 45 degrees allowed
+    triangles.extend(quad(Vec3::new(2.0, 0.0, 0.0), Vec3::new(4.0, 4.0, 3.0)));
+    let ground = generate_ground(&triangles, &coarse());
+
+    assert_eq!(ground.0.len(), 2);
+    assert!(ground.0.iter().flatten().all(|point| point.x <= 2.0));
+}
+
+#[test]
+fn high_steps_split_the_ground() {
+    let mut triangles = quad(Vec3::ZERO, Vec3::new(2.0, 0.0, 3.0)).to_vec();
+    triangles.extend(quad(Vec3::new(2.0, 1.0, 0.0), Vec3::new(4.0, 1.0, 3.0)));
+    let ground = generate_ground(&triangles, &coarse());
+
+    // the cells on both sides of the step are dropped, so the two floors share no edge
+    assert_eq!(ground.0.len(), 4);
+    for point in ground.0.iter().flatten() {
+        match point.x {
+            x if x <= 1.0 => assert_eq!(point.y, 0.0),
+            x if x >= 3.0 => assert_eq!(point.y, 1.0),
+            _ => panic!("ground left at the step: {}", point),
+        }
+    }
+}
+
+#[test]
+fn every_island_of_split_ground_is_walkable() {
+    let mut triangles = quad(Vec3::ZERO, Vec3::new(2.0, 0.0, 3.0)).to_vec();
+    triangles.extend(quad(Vec3::new(2.0, 1.0, 0.0), Vec3::new(4.0, 1.0, 3.0)));
+    let ground = generate_ground(&triangles, &coarse());
+
+    // the two floors are the same size, neither is taken for a hole in the other
+    let islands = ground.islands();
+    assert_eq!(islands.len(), 2);
+    let navmeshes: Vec<NavMesh> = islands
+        .into_iter()
+        .map(|(outer, holes)| NavMesh::from_edge_and_obstacles(outer, holes))
+        .collect();
+    for (from, to) in [
+        (Vec2::new(0.2, 0.5), Vec2::new(0.8, 2.5)),
+        (Vec2::new(3.2, 0.5), Vec2::new(3.8, 2.5)),
+    ] {
+        assert!(navmeshes
+            .iter()
+            .any(|navmesh| navmesh.path(from, to).is_some()));
+    }
+    // walking from one to the other takes a link
+    assert!(navmeshes.iter().all(|navmesh| navmesh
+        .path(Vec2::new(0.5, 1.5), Vec2::new(3.5, 1.5))
+        .is_none()));
+}
+
+#[test]
+fn ground_touching_at_a_corner_keeps_closed_outlines() {
+    // two cells meeting only at (1, 1), and a third one next to the second
+    let mut ground = NavGround::default();
+    ground.0.extend(quad(Vec3::ZERO, Vec3::new(1.0, 0.0, 1.0)));
+    ground
+        .0
+        .extend(quad(Vec3::new(1.0, 0.0, 1.0), Vec3::new(2.0, 0.0, 2.0)));
+    ground
+        .0
+        .extend(quad(Vec3::new(2.0, 0.0, 1.0), Vec3::new(3.0, 0.0, 2.0)));
+    let loops = ground.boundary_loops();
+
+    assert_eq!(loops.len(), 2);
+    assert_eq!(loops[0].len(), 6);
+    assert_eq!(loops[1].len(), 4);
+    for outline in &loops {
+        // every corner is visited once, so the outline neither stops early nor crosses itself
+        for (index, point) in outline.iter().enumerate() {
+            assert!(!outline[index + 1..].contains(point));
+        }
+    }
+    assert!(loops[1].contains(&Vec2::ZERO));
+}
+
+#[test]
+fn merged_ground_stays_in_one_piece() {
+    // an L: the long strip and the square above it are merged apart
+    let mut triangles = quad(Vec3::ZERO, Vec3::new(9.0, 0.0, 1.0)).to_vec();
+    triangles.extend(quad(Vec3::new(0.0, 0.0, 1.0), Vec3::new(4.0, 0.0, 3.0)));
+    let ground = generate_ground(&triangles, &coarse());
+
+    assert!(ground.0.len() < 9 * 2 + 8 * 2);
+    // both rectangles share every vertex along their common side, leaving a single outline
+    let loops = ground.boundary_loops();
+    assert_eq!(loops.len(), 1);
+    // the corners of the L and where the square meets the strip on the left
+    assert_eq!(loops[0].len(), 7);
+}
+
+/// The split floors of `high_steps_split_the_ground` as the level navmesh, with a jump of the
+/// given cost between each pair of points.
+fn follow_app(links: &[(Vec3, Vec3, f32)]) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .init_resource::<Assets<NavMesh>>()
+        .init_resource::<LevelNavMesh>()
+        .init_resource::<PathfindSettings>()
+        .add_event::<PathEvent>()
+        .add_event::<PathNotFound>()
+        .add_event::<PathCompleted>()
+        .add_event::<PathBlocked>()
+        .add_event::<TraverseLink>()
+        .add_systems(
+            Update,
+            (collect_nav_links, get_path, poll_paths, follow_path).chain(),
+        )
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            50,
+        )));
+    app.finish();
+    app.cleanup();
+
+    let mut triangles = quad(Vec3::ZERO, Vec3::new(2.0, 0.0, 3.0)).to_vec();
+    triangles.extend(quad(Vec3::new(2.0, 1.0, 0.0), Vec3::new(4.0, 1.0, 3.0)));
+    let ground = generate_ground(&triangles, &coarse());
+    let navmesh = NavMesh::from_bevy_mesh(&ground.to_mesh()).unwrap();
+    let handle = app
+        .world_mut()
+        .resource_mut::<Assets<NavMesh>>()
+        .add(navmesh);
+    app.world_mut().resource_mut::<LevelNavMesh>().navmesh = Some(handle);
+
+    for (index, (from, to, cost)) in links.iter().enumerate() {
+        let target = format!("ledge {}", index);
+        app.world_mut().spawn((
+            NavLink {
+                to: target.clone(),
+                cost: *cost,
+                bidirectional: false,
+                ..default()
+            },
+            GlobalTransform::from_translation(*from),
+        ));
+        app.world_mut()
+            .spawn((Name::new(target), GlobalTransform::from_translation(*to)));
+    }
+    app
+}
+
+fn spawn_follower(app: &mut App, at: Vec3) -> Entity {
+    app.world_mut()
+        .spawn((Navigator::new(2.0), Transform::from_translation(at)))
+        .id()
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Completed,
+    NotFound,
+    Blocked,
+}
+
+/// whether an event of `entity` was sent since the last call
+fn drained<E: Event>(app: &mut App, entity: Entity, of: fn(&E) -> Entity) -> bool {
+    app.world_mut()
+        .resource_mut::<Events<E>>()
+        .drain()
+        .any(|ev| of(&ev) == entity)
+}
+
+/// runs `app` until `entity` is done with its path, returning how and the links it crossed
+fn follow(app: &mut App, entity: Entity) -> (Outcome, Vec<TraverseLink>) {
+    let mut traversed = Vec::new();
+    for _ in 0..2000 {
+        app.update();
+        traversed.extend(
+            app.world_mut()
+                .resource_mut::<Events<TraverseLink>>()
+                .drain()
+                .filter(|ev| ev.entity == entity),
+        );
+        if drained::<PathCompleted>(app, entity, |ev| ev.0) {
+            return (Outcome::Completed, traversed);
+        }
+        if drained::<PathNotFound>(app, entity, |ev| ev.0) {
+            return (Outcome::NotFound, traversed);
+        }
+        if drained::<PathBlocked>(app, entity, |ev| ev.0) {
+            return (Outcome::Blocked, traversed);
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("{} never finished its path", entity);
+}
+
+fn position(app: &App, entity: Entity) -> Vec2 {
+    app.world()
+        .get::<Transform>(entity)
+        .unwrap()
+        .translation
+        .xz()
+}
+
+#[test]
+fn followers_jump_over_to_the_other_island() {
+    let (from, to) = (Vec3::new(0.8, 0.0, 1.5), Vec3::new(3.2, 1.0, 1.5));
+    let mut app = follow_app(&[(from, to, 1.0)]);
+    let follower = spawn_follower(&mut app, Vec3::new(0.2, 0.0, 0.2));
+    app.world_mut().send_event(PathEvent(
+        follower,
+        Vec3::new(0.2, 0.0, 0.2),
+        Vec3::new(3.8, 1.0, 2.8),
+    ));
+
+    let (outcome, traversed) = follow(&mut app, follower);
+    assert_eq!(outcome, Outcome::Completed);
+    assert_eq!(traversed.len(), 1);
+    assert_eq!((traversed[0].from, traversed[0].to), (from, to));
+    assert!(position(&app, follower).distance(Vec2::new(3.8, 2.8)) < 1e-3);
+}
+
+#[test]
+fn paths_take_the_cheapest_link() {
+    let mut app = follow_app(&[
+        (Vec3::new(0.8, 0.0, 0.5), Vec3::new(3.2, 1.0, 0.5), 20.0),
+        (Vec3::new(0.8, 0.0, 2.5), Vec3::new(3.2, 1.0, 2.5), 1.0),
+    ]);
+    let follower = spawn_follower(&mut app, Vec3::new(0.5, 0.0, 0.5));
+    app.world_mut().send_event(PathEvent(
+        follower,
+        Vec3::new(0.5, 0.0, 0.5),
+        Vec3::new(3.5, 1.0, 0.5),
+    ));
+
+    // walking around to the cheap jump beats the expensive one right there
+    let (outcome, traversed) = follow(&mut app, follower);
+    assert_eq!(outcome, Outcome::Completed);
+    assert_eq!(traversed.len(), 1);
+    assert_eq!(traversed[0].from.z, 2.5);
+}
+
+#[test]
+fn islands_without_links_are_unreachable() {
+    let mut app = follow_app(&[]);
+    let follower = spawn_follower(&mut app, Vec3::new(0.5, 0.0, 0.5));
+    app.world_mut().send_event(PathEvent(
+        follower,
+        Vec3::new(0.5, 0.0, 0.5),
+        Vec3::new(3.5, 1.0, 0.5),
+    ));
+
+    assert_eq!(follow(&mut app, follower).0, Outcome::NotFound);
+}
+
+#[test]
+fn newer_requests_replace_the_query_in_flight() {
+    let mut app = follow_app(&[(Vec3::new(0.8, 0.0, 1.5), Vec3::new(3.2, 1.0, 1.5), 1.0)]);
+    let follower = spawn_follower(&mut app, Vec3::new(0.2, 0.0, 0.2));
+    app.world_mut().send_event(PathEvent(
+        follower,
+        Vec3::new(0.2, 0.0, 0.2),
+        Vec3::new(3.8, 1.0, 2.8),
+    ));
+    app.update();
+    app.world_mut().send_event(PathEvent(
+        follower,
+        Vec3::new(0.2, 0.0, 0.2),
+        Vec3::new(0.5, 0.0, 2.5),
+    ));
+
+    let (outcome, traversed) = follow(&mut app, follower);
+    assert_eq!(outcome, Outcome::Completed);
+    assert!(traversed.is_empty());
+    assert!(position(&app, follower).distance(Vec2::new(0.5, 2.5)) < 1e-3);
+    // the first query never gets to move the follower
+    for _ in 0..20 {
+        app.update();
+    }
+    assert!(!drained::<PathCompleted>(&mut app, follower, |ev| ev.0));
+    assert!(position(&app, follower).distance(Vec2::new(0.5, 2.5)) < 1e-3);
+}
+
+#[test]
+fn waypoints_off_the_navmesh_block_the_path() {
+    let mut app = follow_app(&[]);
+    let follower = spawn_follower(&mut app, Vec3::new(0.5, 0.0, 0.5));
+    // the second waypoint is in the gap left at the step
+    app.world_mut().entity_mut(follower).insert(PathNodes {
+        path: vec![Vec2::new(0.5, 1.5), Vec2::new(2.0, 1.5)],
+        links: Vec::new(),
+        traversing: None,
+    });
+
+    assert_eq!(follow(&mut app, follower).0, Outcome::Blocked);
+    assert!(app.world().get::<PathNodes>(follower).is_none());
+}
@@ -0,0 +1,37 @@
+use hammerspace::ai_controller::components::PatrolMode;
+
+/// the first `steps` markers visited along a route of `len`, starting at the first
+fn walk(mode: PatrolMode, len: usize, steps: usize) -> Vec<usize> {
+    let mut visited = vec![0];
+    let (mut index, mut forward) = (0, true);
+    while visited.len() < steps {
+        let Some(next) = mode.next(index, forward, len) else {
+            break;
+        };
+        (index, forward) = next;
+        visited.push(index);
+    }
+    visited
+}
+
+#[test]
+fn patrol_modes_walk_their_routes() {
+    for mode in [PatrolMode::Once, PatrolMode::Loop, PatrolMode::PingPong] {
+        assert_eq!(mode.next(0, true, 1), None, "{:?} with one marker", mode);
+        assert_eq!(mode.next(0, false, 1), None, "{:?} with one marker", mode);
+    }
+
+    assert_eq!(walk(PatrolMode::Once, 2, 6), [0, 1]);
+    assert_eq!(walk(PatrolMode::Once, 3, 6), [0, 1, 2]);
+
+    assert_eq!(walk(PatrolMode::Loop, 2, 5), [0, 1, 0, 1, 0]);
+    assert_eq!(walk(PatrolMode::Loop, 3, 7), [0, 1, 2, 0, 1, 2, 0]);
+
+    assert_eq!(walk(PatrolMode::PingPong, 2, 5), [0, 1, 0, 1, 0]);
+    assert_eq!(
+        walk(PatrolMode::PingPong, 3, 9),
+        [0, 1, 2, 1, 0, 1, 2, 1, 0]
+    );
+    assert_eq!(PatrolMode::PingPong.next(2, true, 3), Some((1, false)));
+    assert_eq!(PatrolMode::PingPong.next(0, false, 3), Some((1, true)));
+}
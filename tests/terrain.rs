@@ -0,0 +1,121 @@
+#![cfg(feature = "proc_terrain")]
+
+use bevy::prelude::*;
+use hammerspace::terrain::{
+    export::{export_mesh, heightmap_mesh, ExportFormat},
+    loader::{read_raw, HeightmapLoaderError, RawFormat},
+    mesh::{chunk_count, chunk_mesh},
+    noise::{generate, TerrainNoise},
+};
+
+const HILLS: &str = r#"(
+    seed: 42,
+    resolution: (33, 17),
+    layers: [
+        (kind: Fbm, frequency: 4.0, octaves: 5),
+        (kind: Ridged, frequency: 2.0, amplitude: 0.5),
+    ],
+    warp: Some((frequency: 2.0, strength: 0.1)),
+)"#;
+
+#[test]
+fn raw_heightmaps_are_read_and_chunked() {
+    let bytes: Vec<u8> = (0..9u16)
+        .flat_map(|sample| (sample * 8000).to_le_bytes())
+        .collect();
+    let heightmap = read_raw(&bytes, RawFormat::R16, None, Vec2::splat(2.0), 10.0).unwrap();
+    assert_eq!((heightmap.width, heightmap.depth), (3, 3));
+    assert_eq!(heightmap.position(0, 0), Vec3::new(-1.0, 0.0, -1.0));
+    assert!(
+        (heightmap.height_at(Vec2::ZERO).unwrap() - 4.0 * 8000.0 / 65535.0 * 10.0).abs() < 1e-4
+    );
+
+    assert_eq!(chunk_count(&heightmap, 1), UVec2::new(2, 2));
+    let full = chunk_mesh(&heightmap, UVec2::ZERO, 2, 0, 0.0);
+    let coarse = chunk_mesh(&heightmap, UVec2::ZERO, 2, 1, 0.0);
+    assert_eq!(full.count_vertices(), 9);
+    assert_eq!(coarse.count_vertices(), 4);
+
+    let odd = read_raw(&bytes[..10], RawFormat::R16, None, Vec2::ONE, 1.0);
+    assert!(matches!(
+        odd,
+        Err(HeightmapLoaderError::Size { samples: 5, .. })
+    ));
+}
+
+#[test]
+fn r32_heightmaps_are_stretched_to_their_height() {
+    // samples in metres, from 100 to 180
+    let bytes: Vec<u8> = (0..9)
+        .flat_map(|sample| (100.0 + sample as f32 * 10.0).to_le_bytes())
+        .collect();
+    let heightmap = read_raw(&bytes, RawFormat::R32, None, Vec2::splat(2.0), 20.0).unwrap();
+    assert_eq!(heightmap.position(0, 0).y, 0.0);
+    assert_eq!(heightmap.position(2, 2).y, 20.0);
+    assert!((heightmap.height_at(Vec2::ZERO).unwrap() - 10.0).abs() < 1e-4);
+}
+
+#[test]
+fn noise_terrain_is_reproducible() {
+    let mut noise: TerrainNoise = ron::from_str(HILLS).unwrap();
+    let first = generate(&noise, Vec2::splat(64.0), 8.0);
+    let second = generate(&noise, Vec2::splat(64.0), 8.0);
+    assert_eq!((first.width, first.depth), (33, 17));
+    assert_eq!(first.heights, second.heights);
+    assert!(first
+        .heights
+        .iter()
+        .all(|height| (0.0..=1.0).contains(height)));
+    assert!(first.heights.contains(&0.0) && first.heights.contains(&1.0));
+
+    // a handful of samples, these only change when the generator does
+    let snapshot = [
+        (0, 0.6147455),
+        (16, 0.8593843),
+        (100, 0.69056815),
+        (280, 0.7644813),
+        (560, 0.261793),
+    ];
+    for (index, expected) in snapshot {
+        assert!(
+            (first.heights[index] - expected).abs() < 1e-5,
+            "sample {} is {}, expected {}",
+            index,
+            first.heights[index],
+            expected
+        );
+    }
+
+    noise.seed = 43;
+    let reseeded = generate(&noise, Vec2::splat(64.0), 8.0);
+    assert_ne!(first.heights, reseeded.heights);
+}
+
+#[test]
+fn terrain_exports_to_stl_and_glb() {
+    let noise: TerrainNoise = ron::from_str(HILLS).unwrap();
+    let mesh = heightmap_mesh(&generate(&noise, Vec2::splat(64.0), 8.0));
+    let triangles = 32 * 16 * 2;
+
+    let mut stl = Vec::new();
+    export_mesh(&mesh, "hills", ExportFormat::StlBinary, &mut stl).unwrap();
+    assert_eq!(stl.len(), 84 + 50 * triangles);
+
+    let mut ascii = Vec::new();
+    export_mesh(&mesh, "hills", ExportFormat::StlAscii, &mut ascii).unwrap();
+    let ascii = String::from_utf8(ascii).unwrap();
+    assert!(ascii.starts_with("solid hills\n") && ascii.ends_with("endsolid hills\n"));
+    assert_eq!(ascii.matches("facet normal").count(), triangles);
+
+    let mut glb = Vec::new();
+    export_mesh(&mesh, "hills", ExportFormat::Glb, &mut glb).unwrap();
+    assert_eq!(&glb[..4], b"glTF");
+    assert_eq!(
+        u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+        glb.len()
+    );
+    let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+    let document: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+    assert_eq!(document["nodes"][0]["name"], "hills");
+    assert_eq!(document["accessors"][0]["count"], 33 * 17);
+}